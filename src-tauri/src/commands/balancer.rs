@@ -0,0 +1,48 @@
+use crate::{
+    config::{
+        balancer::{group_nodes, validate_group},
+        BalancerGroup, Node,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::info;
+
+/// Nodes which will be used by balancer group
+#[tauri::command]
+pub async fn preview_balancer(group: BalancerGroup) -> VResult<Vec<Node>> {
+    let config = CONFIG.lock().await;
+    Ok(group_nodes(&group, &config.rua)
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+/// Replace balancer group and restart core
+#[tauri::command]
+pub async fn set_balancer(group: BalancerGroup) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    if group.enabled {
+        validate_group(&group)?;
+        let count = group_nodes(&group, &config.rua).len();
+        if count == 0 {
+            return Err(anyhow!("no nodes found for balancer").into());
+        }
+        info!(
+            "Balancer enabled with {} nodes, strategy {}",
+            count,
+            group.strategy.as_str()
+        );
+    } else {
+        info!("Balancer disabled");
+    }
+    config.rua.balancer = group;
+    config.write_rua()?;
+    config.write_runtime()?;
+    drop(config);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use crate::{
+    config::{
+        bind::{list_interfaces, validate_bind, NetInterface},
+        OutboundBind,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use log::info;
+
+/// Network interfaces and their addresses, used to choose bind target
+#[tauri::command]
+pub async fn get_interfaces() -> VResult<Vec<NetInterface>> {
+    Ok(list_interfaces()?)
+}
+
+/// Bind `direct` and proxy outbounds to interface or source address, restart core
+#[tauri::command]
+pub async fn set_outbound_bind(bind: OutboundBind) -> VResult<()> {
+    validate_bind(&bind)?;
+    let mut config = CONFIG.lock().await;
+    config.rua.bind = bind;
+    config.write_rua()?;
+    config.write_runtime()?;
+    drop(config);
+    info!("Outbound bind changed");
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
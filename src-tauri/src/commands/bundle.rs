@@ -0,0 +1,49 @@
+use crate::{
+    config::bundle::{self, BundleManifest, BundlePreview},
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::{error, info};
+use std::path::PathBuf;
+
+/// Export all settings to a bundle file
+///
+/// ## Arguments
+///
+/// `path`: bundle file path
+/// `strip`: remove subscription urls and credentials from bundle
+#[tauri::command]
+pub async fn export_bundle(path: String, strip: bool) -> VResult<BundleManifest> {
+    let config = CONFIG.lock().await;
+    let manifest = bundle::export_bundle(&config, &PathBuf::from(&path), strip)?;
+    info!("Settings exported to {}", path);
+    Ok(manifest)
+}
+
+/// Show what will be replaced by importing the bundle
+#[tauri::command]
+pub async fn preview_bundle(path: String) -> VResult<BundlePreview> {
+    let config = CONFIG.lock().await;
+    Ok(bundle::preview_bundle(&config, &PathBuf::from(path))?)
+}
+
+/// Import settings from bundle file, reload config and restart core
+#[tauri::command]
+pub async fn import_bundle(path: String) -> VResult<BundleManifest> {
+    let mut config = CONFIG.lock().await;
+    let (manifest, backup) = bundle::import_bundle(&config, &PathBuf::from(&path))?;
+    if let Err(err) = config.reload().and_then(|_| config.write_runtime()) {
+        error!("Load imported settings failed {}, revert it", err);
+        backup.restore()?;
+        config.reload()?;
+        config.write_runtime()?;
+        return Err(anyhow!("load imported settings failed {}", err).into());
+    }
+    info!("Settings imported from {}", path);
+    drop(config);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(manifest)
+}
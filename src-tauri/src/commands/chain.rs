@@ -0,0 +1,112 @@
+use crate::{
+    config::{
+        chain::{chain_id, outbounds_by_id, replace_outbounds, validate_chain},
+        ProxyChain, VConfig,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::info;
+
+/// Rebuild `proxy` outbounds if the chain is selected
+///
+/// ## Return
+///
+/// Whether core need to be restarted
+fn reapply_chain(config: &mut VConfig, name: &str) -> VResult<bool> {
+    let id = chain_id(name);
+    if config.rua.current_id != id {
+        return Ok(false);
+    }
+    let outbounds = outbounds_by_id(&id, &config.rua, "proxy")?;
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    replace_outbounds(core, "proxy", outbounds);
+    config.write_core()?;
+    Ok(true)
+}
+
+/// All proxy chains with speed test results
+#[tauri::command]
+pub async fn get_chains() -> VResult<Vec<ProxyChain>> {
+    let config = CONFIG.lock().await;
+    Ok(config.rua.chains.clone())
+}
+
+/// Add proxy chain, select it with `select_node` by id `chain:{name}`
+#[tauri::command]
+pub async fn add_chain(chain: ProxyChain) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    validate_chain(&chain, &config.rua)?;
+    if config.rua.chains.iter().any(|c| c.name == chain.name) {
+        return Err(anyhow!("chain {} already exists", chain.name).into());
+    }
+    info!(
+        "Chain {} added with {} nodes",
+        chain.name,
+        chain.nodes.len()
+    );
+    config.rua.chains.push(chain);
+    config.write_rua()?;
+    drop(config);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    Ok(())
+}
+
+/// Change proxy chain by name, restart core if it's selected
+#[tauri::command]
+pub async fn update_chain(name: String, chain: ProxyChain) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    validate_chain(&chain, &config.rua)?;
+    if chain.name != name && config.rua.chains.iter().any(|c| c.name == chain.name) {
+        return Err(anyhow!("chain {} already exists", chain.name).into());
+    }
+    let target = config
+        .rua
+        .chains
+        .iter_mut()
+        .find(|c| c.name == name)
+        .ok_or(anyhow!("chain {} not found", name))?;
+    let renamed = chain.name.clone();
+    *target = ProxyChain {
+        speed: None,
+        delay: None,
+        connectivity: None,
+        ..chain
+    };
+    if config.rua.current_id == chain_id(&name) {
+        config.rua.current_id = chain_id(&renamed);
+    }
+    let restart = reapply_chain(&mut config, &renamed)?;
+    config.write_rua()?;
+    drop(config);
+    info!("Chain {} updated", name);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    if restart {
+        MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    }
+    Ok(())
+}
+
+/// Delete proxy chain, the selected chain can not be deleted
+#[tauri::command]
+pub async fn delete_chain(name: String) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    if config.rua.current_id == chain_id(&name) {
+        return Err(anyhow!("chain {} is in use, select another node first", name).into());
+    }
+    let len = config.rua.chains.len();
+    config.rua.chains.retain(|c| c.name != name);
+    if config.rua.chains.len() == len {
+        return Err(anyhow!("chain {} not found", name).into());
+    }
+    config.write_rua()?;
+    drop(config);
+    info!("Chain {} deleted", name);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    Ok(())
+}
@@ -1,10 +1,12 @@
 use crate::commands::subs::check_subs_update;
-use crate::config::{CoreConfig, RConfig};
+use crate::config::{balancer::check_reserved_tag, lan::check_lan, CoreConfig, Overlay, RConfig};
+use crate::logger::apply_log_setting;
 use crate::message::{ConfigMsg, MSG_TX};
 use crate::utils::error::VResult;
 use crate::{CONFIG, LOGGING};
 use log::info;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
@@ -17,6 +19,10 @@ pub enum ReturnConfig {
 pub enum WhichConfig {
     Rua,
     Core,
+    /// User overlay file
+    Overlay,
+    /// Core config with overlay applied
+    Runtime,
 }
 /// Return specify config field
 #[tauri::command]
@@ -34,6 +40,8 @@ pub async fn get_config(config_type: WhichConfig) -> VResult<Option<ReturnConfig
             let rua = config.rua.clone();
             Ok(Some(ReturnConfig::Rua(Box::new(rua))))
         }
+        // Only can be read as file
+        WhichConfig::Overlay | WhichConfig::Runtime => Ok(None),
     }
 }
 
@@ -47,7 +55,18 @@ pub async fn update_config(
     use std::sync::atomic::Ordering::Relaxed;
 
     let mut config = CONFIG.lock().await;
+    // shared inbounds should keep authentication
+    let lan = rua_config
+        .as_ref()
+        .map(|r| &r.lan)
+        .unwrap_or(&config.rua.lan);
+    if let Some(core) = core_config.as_ref().or(config.core.as_ref()) {
+        check_lan(&core.inbounds, lan)?;
+    }
     if let Some(c) = core_config {
+        for outbound in &c.outbounds {
+            check_reserved_tag(&outbound.tag)?;
+        }
         info!("Updating core config");
         config.core = Some(c);
         config.write_core()?;
@@ -62,6 +81,7 @@ pub async fn update_config(
         }
         config.rua = r;
         config.write_rua()?;
+        apply_log_setting(&config.log_dir(), &config.rua.settings.log)?;
     }
     check_subs_update(&mut config).await?;
     MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
@@ -74,9 +94,52 @@ pub async fn read_config_file(which: WhichConfig) -> VResult<String> {
     let path = match which {
         WhichConfig::Rua => &config.rua_path,
         WhichConfig::Core => &config.core_path,
+        WhichConfig::Overlay => &config.overlay_path,
+        WhichConfig::Runtime => &config.runtime_path,
     };
+    // overlay file is optional
+    if matches!(which, WhichConfig::Overlay) && !path.exists() {
+        return Ok(serde_json::to_string_pretty(&Overlay::default())?);
+    }
     let mut config_file = File::open(path).await?;
     let mut buffer = String::new();
     config_file.read_to_string(&mut buffer).await?;
     Ok(buffer)
 }
+
+/// Preview the config which core will run with,
+/// the core config with user overlay applied.
+///
+/// ## Arguments
+///
+/// `overlay`: preview with this overlay instead of the overlay file
+#[tauri::command]
+pub async fn preview_core_config(overlay: Option<Overlay>) -> VResult<Value> {
+    let config = CONFIG.lock().await;
+    let runtime = match overlay {
+        Some(overlay) => config.runtime_core_with(&overlay)?,
+        None => config.runtime_core()?,
+    };
+    Ok(runtime)
+}
+
+/// Save user overlay and restart core
+#[tauri::command]
+pub async fn update_overlay(overlay: Overlay) -> VResult<()> {
+    let config = CONFIG.lock().await;
+    let outbounds = overlay
+        .prepend
+        .outbounds
+        .iter()
+        .chain(&overlay.append.outbounds);
+    for tag in outbounds.filter_map(|o| o.get("tag")?.as_str()) {
+        check_reserved_tag(tag)?;
+    }
+    // make sure the overlay can be applied before write it
+    config.runtime_core_with(&overlay)?;
+    info!("Updating core overlay");
+    config.write_overlay(&overlay)?;
+    config.write_runtime()?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
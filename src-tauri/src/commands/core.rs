@@ -1,12 +1,20 @@
 use crate::{
-    config::{find_node, proxy_builder},
+    config::{
+        chain::{outbounds_by_id, replace_outbounds},
+        ProxyMode,
+    },
     message::{ConfigMsg, MSG_TX},
     utils::error::VResult,
-    CONFIG,
+    CONFIG, UI,
 };
 use anyhow::anyhow;
+use log::info;
 
-/// Active select node from frontend
+/// Active select node or chain from frontend, balancer group will be disabled
+///
+/// ## Arguments
+///
+/// `node_id`: node id, or chain id like `chain:{name}`
 #[tauri::command]
 pub async fn select_node(node_id: String) -> VResult<()> {
     let mut config = CONFIG.lock().await;
@@ -14,22 +22,18 @@ pub async fn select_node(node_id: String) -> VResult<()> {
     let rua = &mut config.rua;
     let core = &mut config.core;
 
-    let node = find_node(&node_id, rua)?;
+    let outbounds = outbounds_by_id(&node_id, rua, "proxy")?;
     let core = core
         .as_mut()
         .ok_or(anyhow!("cannont found config config"))?;
-    let proxy_outbound = core
-        .outbounds
-        .iter()
-        .position(|outbound| outbound.tag == "proxy");
-    if let Some(index) = proxy_outbound {
-        core.outbounds[index] = proxy_builder(node, "proxy".into())?;
-    } else {
-        core.outbounds
-            .insert(0, proxy_builder(node, "proxy".into())?)
-    }
+    replace_outbounds(core, "proxy", outbounds);
     config.write_core()?;
     config.rua.current_id = node_id;
+    if config.rua.balancer.enabled {
+        config.rua.balancer.enabled = false;
+        config.write_runtime()?;
+        info!("Balancer disabled by selecting node");
+    }
     config.write_rua()?;
     MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
     Ok(())
@@ -40,3 +44,18 @@ pub async fn restart_core() -> VResult<()> {
     MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
     Ok(())
 }
+
+/// Switch routing mode and restart core,
+/// rules in core config are kept and used again in rule mode
+#[tauri::command]
+pub async fn set_proxy_mode(mode: ProxyMode) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    config.rua.mode = mode;
+    config.write_rua()?;
+    config.write_runtime()?;
+    drop(config);
+    UI.lock().await.mode = mode;
+    info!("Routing mode changed to {}", mode.as_str());
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
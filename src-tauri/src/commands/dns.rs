@@ -0,0 +1,101 @@
+use crate::{
+    commands::check_problems,
+    config::{
+        dns::{parse_hosts, system_hosts_path, validate_dns, validate_hosts, validate_servers},
+        Dns, DnsServer, HostAddress,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::info;
+use std::collections::BTreeMap;
+use tokio::fs;
+
+/// Validate DNS config from frontend
+///
+/// ## Return
+///
+/// All problems found in config, empty if config is valid
+#[tauri::command]
+pub async fn check_dns(dns: Dns) -> VResult<Vec<String>> {
+    Ok(validate_dns(&dns))
+}
+
+/// Replace whole DNS config and restart core
+#[tauri::command]
+pub async fn update_dns(dns: Dns) -> VResult<()> {
+    check_problems("dns config", validate_dns(&dns))?;
+    let mut config = CONFIG.lock().await;
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    core.dns = dns;
+    config.write_core()?;
+    info!("DNS config updated");
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// Replace DNS servers and restart core
+#[tauri::command]
+pub async fn update_dns_servers(servers: Vec<DnsServer>) -> VResult<()> {
+    check_problems("dns config", validate_servers(&servers))?;
+    let mut config = CONFIG.lock().await;
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    core.dns.servers = servers;
+    config.write_core()?;
+    info!("DNS servers updated");
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// Replace DNS hosts and restart core
+#[tauri::command]
+pub async fn update_dns_hosts(hosts: BTreeMap<String, HostAddress>) -> VResult<()> {
+    check_problems("dns config", validate_hosts(&hosts))?;
+    let mut config = CONFIG.lock().await;
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    core.dns.hosts = hosts;
+    config.write_core()?;
+    info!("DNS hosts updated");
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// Import hosts file into DNS hosts, existing hosts with same name will be replaced
+///
+/// ## Arguments
+///
+/// `path`: hosts file path, use system hosts file if it's empty
+///
+/// ## Return
+///
+/// Count of imported hosts
+#[tauri::command]
+pub async fn import_hosts(path: Option<String>) -> VResult<usize> {
+    let path = path.map(Into::into).unwrap_or_else(system_hosts_path);
+    let content = fs::read_to_string(&path).await?;
+    let hosts = parse_hosts(&content);
+    check_problems("dns config", validate_hosts(&hosts))?;
+    let count = hosts.len();
+
+    let mut config = CONFIG.lock().await;
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    core.dns.hosts.extend(hosts);
+    config.write_core()?;
+    info!("Imported {} hosts from {}", count, path.display());
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(count)
+}
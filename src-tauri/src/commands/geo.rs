@@ -0,0 +1,291 @@
+use crate::{
+    geo::{
+        asset_file,
+        dat::{list_codes, load_geosites, search_geoip, search_geosite, GeoCode, GeoSite},
+        download_geo, geo_url, record_rollback, record_version, rollback_geo, GEOIP, GEOSITE,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::{anyhow, bail, Result};
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::SystemTime};
+use tokio::{fs, sync::Mutex};
+
+/// Max count of completions returned
+const COMPLETION_LIMIT: usize = 50;
+
+/// Geosite category contains the searched domain
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoSiteHit {
+    /// Matcher used in routing rules, like `geosite:google`
+    pub matcher: String,
+    /// The entry matches domain, like `domain:google.com`
+    pub entry: String,
+    pub attributes: Vec<String>,
+}
+
+/// Geoip country contains the searched IP
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoIpHit {
+    /// Matcher used in routing rules, like `geoip:us`
+    pub matcher: String,
+    pub cidr: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoSearch {
+    pub sites: Vec<GeoSiteHit>,
+    pub ips: Vec<GeoIpHit>,
+}
+
+/// Geo data file and what's parsed from it,
+/// parsed again when the file is updated or rolled back
+struct GeoFile {
+    /// Modified time and size of file
+    stamp: (SystemTime, u64),
+    data: Arc<Vec<u8>>,
+    codes: Option<Arc<Vec<GeoCode>>>,
+    sites: Option<Arc<Vec<GeoSite>>>,
+}
+
+/// Parsed geo data files, so searching and completion don't parse them on every call
+static GEO_FILES: Lazy<Mutex<HashMap<&'static str, GeoFile>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Read geo data file, or get the cached one if it's not changed
+async fn load_geo<'a>(
+    files: &'a mut HashMap<&'static str, GeoFile>,
+    name: &str,
+) -> Result<&'a mut GeoFile> {
+    let name = match name {
+        GEOIP => GEOIP,
+        GEOSITE => GEOSITE,
+        _ => bail!("unknown geo data file {}", name),
+    };
+    let path = asset_file(name);
+    let meta = fs::metadata(&path)
+        .await
+        .map_err(|e| anyhow!("read geo data {} failed {}", path.display(), e))?;
+    let stamp = (meta.modified()?, meta.len());
+    if files.get(name).map(|f| f.stamp) != Some(stamp) {
+        let data = fs::read(&path)
+            .await
+            .map_err(|e| anyhow!("read geo data {} failed {}", path.display(), e))?;
+        let file = GeoFile {
+            stamp,
+            data: Arc::new(data),
+            codes: None,
+            sites: None,
+        };
+        files.insert(name, file);
+    }
+    files
+        .get_mut(name)
+        .ok_or(anyhow!("geo data {} is not loaded", name))
+}
+
+async fn read_geo(name: &str) -> Result<Arc<Vec<u8>>> {
+    let mut files = GEO_FILES.lock().await;
+    Ok(load_geo(&mut files, name).await?.data.clone())
+}
+
+/// Codes of geo data file with entry count, in the order of file
+async fn geo_codes(name: &str) -> Result<Arc<Vec<GeoCode>>> {
+    let mut files = GEO_FILES.lock().await;
+    let file = load_geo(&mut files, name).await?;
+    if file.codes.is_none() {
+        file.codes = Some(Arc::new(list_codes(&file.data)?));
+    }
+    file.codes
+        .clone()
+        .ok_or(anyhow!("codes of {} is not loaded", name))
+}
+
+/// All categories of `geosite.dat`
+async fn geo_sites() -> Result<Arc<Vec<GeoSite>>> {
+    let mut files = GEO_FILES.lock().await;
+    let file = load_geo(&mut files, GEOSITE).await?;
+    if file.sites.is_none() {
+        file.sites = Some(Arc::new(load_geosites(&file.data)?));
+    }
+    file.sites
+        .clone()
+        .ok_or(anyhow!("categories of {} is not loaded", GEOSITE))
+}
+
+/// Download newest geo data files and restart core,
+/// config is not locked while downloading
+///
+/// ## Arguments
+///
+/// `name`: `geoip.dat` or `geosite.dat`, update both if it's empty
+#[tauri::command]
+pub async fn update_geo_data(name: Option<String>) -> VResult<()> {
+    let names = match name.as_deref() {
+        Some(name) => vec![name],
+        None => vec![GEOIP, GEOSITE],
+    };
+    let config = CONFIG.lock().await;
+    let asset_dir = config.asset_dir();
+    let urls = names
+        .into_iter()
+        .map(|name| Ok((name, geo_url(&config.rua.geo, name)?.to_owned())))
+        .collect::<Result<Vec<_>>>()?;
+    drop(config);
+
+    // Files downloaded before a failure are already replaced,
+    // so they are recorded and applied before returning the error
+    let mut versions = vec![];
+    let mut failed = None;
+    for (name, url) in urls {
+        match download_geo(&asset_dir, name, &url).await {
+            Ok(version) => versions.push((name, version)),
+            Err(e) => {
+                failed = Some(anyhow!("update geo data {} failed {}", name, e));
+                break;
+            }
+        }
+    }
+
+    if !versions.is_empty() {
+        let mut config = CONFIG.lock().await;
+        for (name, version) in versions {
+            record_version(&mut config.rua.geo, name, version);
+        }
+        config.write_rua()?;
+        drop(config);
+        MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    }
+    match failed {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+/// Restore previous version of geo data file and restart core
+#[tauri::command]
+pub async fn rollback_geo_data(name: String) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    rollback_geo(&config.asset_dir(), &name)?;
+    record_rollback(&mut config.rua.geo, &name);
+    config.write_rua()?;
+    info!("Rollback geo data {} done", name);
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// List categories of `geosite.dat` or country codes of `geoip.dat`
+///
+/// ## Arguments
+///
+/// `name`: `geoip.dat` or `geosite.dat`
+///
+/// ## Return
+///
+/// Codes with entry count, sorted by code
+#[tauri::command]
+pub async fn get_geo_codes(name: String) -> VResult<Vec<GeoCode>> {
+    let mut codes = geo_codes(&name).await?.as_ref().clone();
+    codes.sort_by(|a, b| a.code.cmp(&b.code));
+    Ok(codes)
+}
+
+/// Find which geosite categories contain the domain,
+/// or which geoip countries contain the IP
+///
+/// ## Arguments
+///
+/// `query`: domain or IP
+#[tauri::command]
+pub async fn search_geo_data(query: String) -> VResult<GeoSearch> {
+    let query = query.trim().to_lowercase();
+    let mut result = GeoSearch::default();
+    if query.is_empty() {
+        return Ok(result);
+    }
+    match query.parse::<IpAddr>() {
+        Ok(ip) => {
+            let data = read_geo(GEOIP).await?;
+            result.ips = search_geoip(&data, &ip)?
+                .into_iter()
+                .map(|(code, cidr)| GeoIpHit {
+                    matcher: format!("geoip:{}", code),
+                    cidr: cidr.to_string(),
+                })
+                .collect();
+        }
+        Err(_) => {
+            let sites = geo_sites().await?;
+            result.sites = search_geosite(&sites, &query)
+                .into_iter()
+                .map(|(code, domain)| GeoSiteHit {
+                    matcher: format!("geosite:{}", code),
+                    entry: domain.matcher(),
+                    attributes: domain.attributes,
+                })
+                .collect();
+        }
+    }
+    Ok(result)
+}
+
+/// Complete `geosite:` and `geoip:` matchers in routing rule editor
+///
+/// ## Arguments
+///
+/// `input`: matcher being typed, like `geosite:categ` or `geosite:google@`
+///
+/// ## Return
+///
+/// Valid matchers start with input, empty if input is not a geo matcher
+#[tauri::command]
+pub async fn complete_geo_matcher(input: String) -> VResult<Vec<String>> {
+    let input = input.trim().to_lowercase();
+    let (prefix, name, partial) = if let Some(code) = input.strip_prefix("geosite:") {
+        ("geosite:", GEOSITE, code)
+    } else if let Some(code) = input.strip_prefix("geoip:!") {
+        ("geoip:!", GEOIP, code)
+    } else if let Some(code) = input.strip_prefix("geoip:") {
+        ("geoip:", GEOIP, code)
+    } else {
+        return Ok(vec![]);
+    };
+    // Attributes of category, like `geosite:google@cn`
+    if let (GEOSITE, Some((code, attribute))) = (name, partial.split_once('@')) {
+        let sites = geo_sites().await?;
+        let site = match sites.iter().find(|s| s.code.eq_ignore_ascii_case(code)) {
+            Some(site) => site,
+            None => return Ok(vec![]),
+        };
+        return Ok(site
+            .attributes()
+            .into_iter()
+            .filter(|a| a.starts_with(attribute))
+            .take(COMPLETION_LIMIT)
+            .map(|a| format!("{}{}@{}", prefix, code, a))
+            .collect());
+    }
+
+    let mut codes = geo_codes(name)
+        .await?
+        .iter()
+        .map(|c| c.code.clone())
+        .filter(|c| c.contains(partial))
+        .collect::<Vec<_>>();
+    // Codes start with input first
+    codes.sort_by(|a, b| {
+        (!a.starts_with(partial), a.len(), a).cmp(&(!b.starts_with(partial), b.len(), b))
+    });
+    Ok(codes
+        .into_iter()
+        .take(COMPLETION_LIMIT)
+        .map(|c| format!("{}{}", prefix, c))
+        .collect())
+}
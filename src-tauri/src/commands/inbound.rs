@@ -0,0 +1,155 @@
+use crate::{
+    commands::check_problems,
+    config::{
+        inbound::{
+            check_ports, listen_address, next_free_port, normalize_auth, validate_inbound,
+            PortStatus, PROTECTED_INBOUNDS,
+        },
+        lan::check_lan,
+        Inbound, Port,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG, CORE,
+};
+use anyhow::anyhow;
+use log::{info, warn};
+use std::slice;
+
+/// Pid of running core process
+async fn core_pid() -> Option<u32> {
+    CORE.lock().await.child.as_ref().map(|child| child.pid())
+}
+
+/// Check whether ports of inbounds can be listened
+///
+/// ## Arguments
+///
+/// `inbounds`: inbounds to be checked, check inbounds in core config if it's None
+#[tauri::command]
+pub async fn check_inbound_ports(inbounds: Option<Vec<Inbound>>) -> VResult<Vec<PortStatus>> {
+    let pid = core_pid().await;
+    let config = CONFIG.lock().await;
+    let core = config
+        .core
+        .as_ref()
+        .ok_or(anyhow!("cannot found core config"))?;
+    let inbounds = inbounds.as_ref().unwrap_or(&core.inbounds);
+    Ok(check_ports(inbounds, &core.inbounds, pid))
+}
+
+/// Add or replace inbound in core config and restart core
+///
+/// ## Arguments
+///
+/// `tag`: tag of the inbound to be replaced, add new inbound if it's None
+/// `inbound`: new inbound
+/// `auto_port`: use next free port if the port is held by other process
+///
+/// ## Return
+///
+/// The applied inbound, port may be changed
+async fn apply_inbound(tag: Option<String>, inbound: Inbound, auto_port: bool) -> VResult<Inbound> {
+    let mut inbound = inbound;
+    let pid = core_pid().await;
+    let mut config = CONFIG.lock().await;
+    let lan = config.rua.lan.clone();
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    let index = match &tag {
+        Some(tag) => Some(
+            core.inbounds
+                .iter()
+                .position(|i| &i.tag == tag)
+                .ok_or(anyhow!("inbound {} not found", tag))?,
+        ),
+        None => None,
+    };
+    let others = core
+        .inbounds
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != index)
+        .map(|(_, inbound)| inbound.clone())
+        .collect::<Vec<_>>();
+    normalize_auth(&mut inbound);
+    check_problems("inbound", validate_inbound(&inbound, &others))?;
+
+    let status = check_ports(slice::from_ref(&inbound), &core.inbounds, pid);
+    if let Some(status) = status.into_iter().find(|s| !s.free) {
+        let owner = status
+            .owner
+            .map(|o| format!("{} (pid {})", o.name, o.pid))
+            .unwrap_or_else(|| "other process".into());
+        if !auto_port {
+            return Err(anyhow!("port {} is used by {}", status.port, owner).into());
+        }
+        let taken = others
+            .iter()
+            .filter_map(|i| i.port.number())
+            .collect::<Vec<_>>();
+        let port = next_free_port(listen_address(&inbound), status.port, &taken)
+            .ok_or(anyhow!("no free port after {}", status.port))?;
+        warn!(
+            "Port {} is used by {}, use port {} for inbound {}",
+            status.port, owner, port, inbound.tag
+        );
+        inbound.port = Port::Number(port);
+    }
+
+    let mut inbounds = core.inbounds.clone();
+    match index {
+        Some(index) => inbounds[index] = inbound.clone(),
+        None => inbounds.push(inbound.clone()),
+    }
+    check_lan(&inbounds, &lan)?;
+    core.inbounds = inbounds;
+    config.write_core()?;
+    info!("Inbound {} applied", inbound.tag);
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(inbound)
+}
+
+/// Add new inbound
+#[tauri::command]
+pub async fn add_inbound(inbound: Inbound, auto_port: bool) -> VResult<Inbound> {
+    apply_inbound(None, inbound, auto_port).await
+}
+
+/// Replace inbound by tag
+#[tauri::command]
+pub async fn update_inbound(tag: String, inbound: Inbound, auto_port: bool) -> VResult<Inbound> {
+    if PROTECTED_INBOUNDS.contains(&tag.as_str()) && inbound.tag != tag {
+        return Err(anyhow!("inbound {} is used by Venus and cannot be renamed", tag).into());
+    }
+    apply_inbound(Some(tag), inbound, auto_port).await
+}
+
+/// Remove inbound by tag
+#[tauri::command]
+pub async fn remove_inbound(tag: String) -> VResult<()> {
+    if PROTECTED_INBOUNDS.contains(&tag.as_str()) {
+        return Err(anyhow!("inbound {} is used by Venus and cannot be removed", tag).into());
+    }
+    let mut config = CONFIG.lock().await;
+    let lan = config.rua.lan.clone();
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    let index = core
+        .inbounds
+        .iter()
+        .position(|i| i.tag == tag)
+        .ok_or(anyhow!("inbound {} not found", tag))?;
+    let mut inbounds = core.inbounds.clone();
+    inbounds.remove(index);
+    check_lan(&inbounds, &lan)?;
+    core.inbounds = inbounds;
+    config.write_core()?;
+    info!("Inbound {} removed", tag);
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use crate::{
+    config::lan::{self, lan_addresses, share_urls, LanAddress, ShareUrl},
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+/// LAN sharing state for frontend
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LanInfo {
+    pub enabled: bool,
+    pub address: String,
+    pub inbounds: Vec<String>,
+    /// Addresses of this machine
+    pub addresses: Vec<LanAddress>,
+    /// Ready to copy proxy urls for other devices
+    pub urls: Vec<ShareUrl>,
+}
+
+async fn lan_info() -> VResult<LanInfo> {
+    let config = CONFIG.lock().await;
+    let core = config
+        .core
+        .as_ref()
+        .ok_or(anyhow!("cannot found core config"))?;
+    let setting = &config.rua.lan;
+    Ok(LanInfo {
+        enabled: setting.enabled,
+        address: setting.address.clone(),
+        inbounds: setting.inbounds.clone(),
+        addresses: lan_addresses()?,
+        urls: share_urls(core, setting)?,
+    })
+}
+
+/// LAN addresses and sharing state
+#[tauri::command]
+pub async fn get_lan_info() -> VResult<LanInfo> {
+    lan_info().await
+}
+
+/// Turn LAN sharing on or off and restart core
+///
+/// ## Arguments
+///
+/// `enabled`: share inbounds or restore them
+/// `address`: listen address, default is "0.0.0.0"
+/// `inbounds`: tags of inbounds to share, keep previous ones if it's None
+#[tauri::command]
+pub async fn set_lan_sharing(
+    enabled: bool,
+    address: Option<String>,
+    inbounds: Option<Vec<String>>,
+) -> VResult<LanInfo> {
+    let mut guard = CONFIG.lock().await;
+    let config = &mut *guard;
+    let core = config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?;
+    let mut setting = config.rua.lan.clone();
+    if let Some(address) = address {
+        setting.address = address;
+    }
+    if let Some(inbounds) = inbounds {
+        setting.inbounds = inbounds;
+    }
+    if enabled {
+        // core config is only changed when all inbounds are valid
+        let mut new_core = core.clone();
+        lan::enable_lan(&mut new_core, &mut setting)?;
+        *core = new_core;
+        info!("LAN sharing enabled on {}", setting.address);
+    } else {
+        lan::disable_lan(core, &mut setting);
+        info!("LAN sharing disabled");
+    }
+    config.rua.lan = setting;
+    config.write_core()?;
+    config.write_rua()?;
+    drop(guard);
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    lan_info().await
+}
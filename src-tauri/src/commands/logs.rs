@@ -0,0 +1,34 @@
+use crate::{
+    logger::{last_lines, set_level, LogLine, LogSource},
+    utils::error::VResult,
+    CONFIG,
+};
+use log::info;
+
+/// Get last lines of logs in memory
+///
+/// ## Arguments
+///
+/// `lines`: how many lines to return
+/// `level`: only return lines which level is equal or more severe than it
+/// `source`: only return lines from `App` or `Core`
+#[tauri::command]
+pub async fn get_logs(
+    lines: usize,
+    level: Option<String>,
+    source: Option<LogSource>,
+) -> VResult<Vec<LogLine>> {
+    let logs = last_lines(lines, level.as_deref(), source)?;
+    Ok(logs)
+}
+
+/// Change log level at runtime and save it to config
+#[tauri::command]
+pub async fn set_log_level(level: String) -> VResult<()> {
+    set_level(&level)?;
+    let mut config = CONFIG.lock().await;
+    config.rua.settings.log.level = level;
+    config.write_rua()?;
+    info!("Log level changed to {}", config.rua.settings.log.level);
+    Ok(())
+}
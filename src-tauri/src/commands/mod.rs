@@ -1,5 +1,10 @@
 use crate::{
-    config::{change_connectivity, find_node, proxy_builder, Rule},
+    config::{
+        chain::{find_measured, outbounds_by_id, replace_outbounds},
+        change_connectivity,
+        inbound::proxy_url,
+        Rule,
+    },
     core::{CoreMessage, CORE_MSG_TX},
     event::{RUAEvents, SpeedTestPayload},
     message::{ConfigMsg, MSG_TX},
@@ -15,11 +20,39 @@ use tauri::Window;
 use tokio::time::{sleep, Duration, Instant};
 use url::Url;
 
+pub mod balancer;
+pub mod bind;
+pub mod bundle;
+pub mod chain;
 pub mod config;
 pub mod core;
+pub mod dns;
+pub mod geo;
+pub mod inbound;
+pub mod lan;
+pub mod logs;
+pub mod node_override;
+pub mod profile;
+pub mod routing;
+pub mod rule_set;
+pub mod snapshot;
 pub mod subs;
 pub mod ui;
 
+/// Turn validation problems into error
+///
+/// ## Arguments
+///
+/// `what`: the validated thing, used in error message
+/// `errors`: problems found by validation
+pub fn check_problems(what: &str, errors: Vec<String>) -> VResult<()> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("invalid {}: {}", what, errors.join("; ")).into())
+    }
+}
+
 pub async fn speed_test(proxy: &str, node_id: String) -> Result<()> {
     let start = Instant::now();
     let http = reqwest::Proxy::http(proxy)?;
@@ -41,16 +74,8 @@ pub async fn speed_test(proxy: &str, node_id: String) -> Result<()> {
     });
 
     let download_start = Instant::now();
-    let rua = &mut config.rua;
-    let mut node = None;
-    rua.subscriptions.iter_mut().for_each(|sub| {
-        node = sub
-            .nodes
-            .iter_mut()
-            .find(|n| n.node_id.as_ref().unwrap_or(&"".to_string()) == &node_id);
-    });
-    let node = node.ok_or(anyhow!("node {} not found", node_id))?;
-    node.delay = Some(latency as u64);
+    let mut node = find_measured(&node_id, &mut config.rua)?;
+    node.set_delay(latency as u64);
     while let Ok(Some(c)) = response.chunk().await {
         // milliseconds
         let time = download_start.elapsed().as_nanos() as f64 / 1_000_000_000_f64;
@@ -59,14 +84,14 @@ pub async fn speed_test(proxy: &str, node_id: String) -> Result<()> {
 
         let speed = bytes_per_second / 1_000_000_f64;
         let speed = format!("{:.2}", speed).parse().unwrap_or(speed);
-        node.speed = Some(speed);
+        node.set_speed(speed);
         let percentage = {
             let p = (len as f64) / (total as f64) * 100.0;
             p.round() as u8
         };
         info!(
             "Node {} download speed {} MB/s, {}%",
-            node.add, speed, percentage
+            node_id, speed, percentage
         );
         // MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
     }
@@ -76,11 +101,11 @@ pub async fn speed_test(proxy: &str, node_id: String) -> Result<()> {
     Ok(())
 }
 
-/// Test selected node speed
+/// Test selected node or chain speed
 ///
 /// ## Arguments
 ///
-/// `node_id`: selected node id
+/// `node_id`: selected node id, or chain id like `chain:{name}`
 /// `window`: tauri window
 #[tauri::command]
 pub async fn node_speed(node_id: String, window: Window) -> VResult<()> {
@@ -91,16 +116,8 @@ pub async fn node_speed(node_id: String, window: Window) -> VResult<()> {
     let core = core.as_mut().ok_or(anyhow!("cannot found config config"))?;
 
     // Change speed outbound
-    let node = find_node(&node_id, rua)?;
-    let speed_outbound = core
-        .outbounds
-        .iter()
-        .position(|outbound| outbound.tag == "speed");
-    if let Some(index) = speed_outbound {
-        core.outbounds[index] = proxy_builder(node, "speed".into())?;
-    } else {
-        core.outbounds.push(proxy_builder(node, "speed".into())?);
-    }
+    let outbounds = outbounds_by_id(&node_id, rua, "speed")?;
+    replace_outbounds(core, "speed", outbounds);
     // add speed outbound routing rule
     let rule_index = core
         .routing
@@ -142,7 +159,7 @@ pub async fn node_speed(node_id: String, window: Window) -> VResult<()> {
         .iter()
         .find(|inbound| inbound.tag == "socks")
         .ok_or(anyhow!("cannot find socks inbound"))?;
-    let proxy = format!("socks5://{}:{}", target_proxy.listen, target_proxy.port);
+    let proxy = proxy_url(target_proxy)?;
     drop(config);
 
     let mut rx = CORE_MSG_TX.subscribe();
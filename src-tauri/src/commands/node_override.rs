@@ -0,0 +1,103 @@
+use crate::{
+    config::{
+        chain::{outbounds_by_id, replace_outbounds},
+        find_node,
+        node_override::validate_override,
+        NodeOverrides, OutboundOverride, VConfig,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::{info, warn};
+use std::collections::BTreeMap;
+
+/// Insert or remove override, empty override is removed
+fn put_override(
+    map: &mut BTreeMap<String, OutboundOverride>,
+    key: String,
+    value: Option<OutboundOverride>,
+) -> VResult<()> {
+    match value {
+        Some(value) if value != OutboundOverride::default() => {
+            validate_override(&value)?;
+            map.insert(key, value);
+        }
+        _ => {
+            map.remove(&key);
+        }
+    }
+    Ok(())
+}
+
+/// Rebuild `proxy` outbounds of selected node and write config files.
+/// Balancer outbounds are generated in runtime config so they are rebuilt too.
+fn rebuild_proxy(config: &mut VConfig) -> VResult<()> {
+    let current = config.rua.current_id.clone();
+    if !current.is_empty() {
+        match outbounds_by_id(&current, &config.rua, "proxy") {
+            Ok(outbounds) => {
+                let core = config
+                    .core
+                    .as_mut()
+                    .ok_or(anyhow!("cannot found core config"))?;
+                replace_outbounds(core, "proxy", outbounds);
+            }
+            Err(e) => warn!("Rebuild outbound of selected node failed {}", e),
+        }
+    }
+    config.write_core()?;
+    config.write_rua()?;
+    Ok(())
+}
+
+/// All node and subscription overrides
+#[tauri::command]
+pub async fn get_node_overrides() -> VResult<NodeOverrides> {
+    let config = CONFIG.lock().await;
+    Ok(config.rua.overrides.clone())
+}
+
+/// Set outbound override of node and restart core
+///
+/// ## Arguments
+///
+/// `node_id`: node id
+/// `value`: the override, remove it if None or empty
+#[tauri::command]
+pub async fn set_node_override(node_id: String, value: Option<OutboundOverride>) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    find_node(&node_id, &config.rua)?;
+    put_override(&mut config.rua.overrides.nodes, node_id.clone(), value)?;
+    rebuild_proxy(&mut config)?;
+    drop(config);
+    info!("Override of node {} changed", node_id);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// Set default outbound override of subscription nodes and restart core
+///
+/// ## Arguments
+///
+/// `name`: subscription name
+/// `value`: the override, remove it if None or empty
+#[tauri::command]
+pub async fn set_subscription_override(
+    name: String,
+    value: Option<OutboundOverride>,
+) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    if !config.rua.subscriptions.iter().any(|s| s.name == name) {
+        return Err(anyhow!("subscription {} not found", name).into());
+    }
+    put_override(&mut config.rua.overrides.subscriptions, name.clone(), value)?;
+    rebuild_proxy(&mut config)?;
+    drop(config);
+    info!("Override of subscription {} changed", name);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use crate::{
+    config::profile::{self, list_profiles, load_profile, profile_exists, save_profile, Profile},
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::info;
+
+/// All saved profiles
+#[tauri::command]
+pub async fn get_profiles() -> VResult<Vec<Profile>> {
+    let config = CONFIG.lock().await;
+    Ok(list_profiles(&config.profile_dir())?)
+}
+
+/// Save current routing, DNS, overlay and selected node as a new profile
+///
+/// ## Arguments
+///
+/// `name`: new profile name
+#[tauri::command]
+pub async fn create_profile(name: String) -> VResult<Profile> {
+    let config = CONFIG.lock().await;
+    let dir = config.profile_dir();
+    if profile_exists(&dir, &name)? {
+        return Err(anyhow!("profile {} already exists", name).into());
+    }
+    let profile = config.capture_profile(&name)?;
+    save_profile(&dir, &profile)?;
+    info!("Profile {} created", name);
+    Ok(profile)
+}
+
+/// Copy exist profile with new name
+///
+/// ## Arguments
+///
+/// `from`: source profile name
+/// `name`: new profile name
+#[tauri::command]
+pub async fn clone_profile(from: String, name: String) -> VResult<Profile> {
+    let config = CONFIG.lock().await;
+    let dir = config.profile_dir();
+    if profile_exists(&dir, &name)? {
+        return Err(anyhow!("profile {} already exists", name).into());
+    }
+    // the active profile may be changed after activation
+    let mut profile = if config.rua.profile == from {
+        config.capture_profile(&from)?
+    } else {
+        load_profile(&dir, &from)?
+    };
+    profile.name = name;
+    save_profile(&dir, &profile)?;
+    info!("Profile {} cloned to {}", from, profile.name);
+    Ok(profile)
+}
+
+/// Delete profile, current config files will not be changed
+#[tauri::command]
+pub async fn delete_profile(name: String) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    profile::delete_profile(&config.profile_dir(), &name)?;
+    if config.rua.profile == name {
+        config.rua.profile = String::new();
+        config.write_rua()?;
+        drop(config);
+        MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    }
+    info!("Profile {} deleted", name);
+    Ok(())
+}
+
+/// Activate profile, regenerate config files and restart core
+#[tauri::command]
+pub async fn activate_profile(name: String) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    config.activate_profile(&name)?;
+    drop(config);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use crate::{
+    commands::check_problems,
+    config::{
+        routing::{validate_rule, RouteTargets},
+        simulate::{self, RouteRequest, RouteResult},
+        Rule, VConfig,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::info;
+
+/// Validate rule against current core config
+fn check_rule_in(config: &VConfig, rule: &Rule) -> VResult<Vec<String>> {
+    let targets = RouteTargets::from_runtime(&config.runtime_core()?);
+    Ok(validate_rule(rule, &targets))
+}
+
+/// Write core config and restart core
+async fn apply_rules(config: &mut VConfig) -> VResult<()> {
+    config.write_core()?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// Check index of routing rules
+fn check_index(len: usize, index: usize) -> VResult<()> {
+    if index >= len {
+        return Err(anyhow!("rule index {} out of range, total {}", index, len).into());
+    }
+    Ok(())
+}
+
+/// Validate routing rule
+///
+/// ## Return
+///
+/// All problems found in rule, empty if rule is valid
+#[tauri::command]
+pub async fn check_rule(rule: Rule) -> VResult<Vec<String>> {
+    let config = CONFIG.lock().await;
+    check_rule_in(&config, &rule)
+}
+
+/// Insert routing rule
+///
+/// ## Arguments
+///
+/// `rule`: new rule
+/// `index`: insert position, append to the end if it's None
+#[tauri::command]
+pub async fn add_rule(rule: Rule, index: Option<usize>) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    check_problems("rule", check_rule_in(&config, &rule)?)?;
+    let rules = &mut config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?
+        .routing
+        .rules;
+    let index = index.unwrap_or(rules.len()).min(rules.len());
+    rules.insert(index, rule);
+    info!("Routing rule added at {}", index);
+    apply_rules(&mut config).await
+}
+
+/// Replace routing rule at index
+#[tauri::command]
+pub async fn update_rule(index: usize, rule: Rule) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    check_problems("rule", check_rule_in(&config, &rule)?)?;
+    let rules = &mut config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?
+        .routing
+        .rules;
+    check_index(rules.len(), index)?;
+    rules[index] = rule;
+    info!("Routing rule {} updated", index);
+    apply_rules(&mut config).await
+}
+
+/// Delete routing rule at index
+#[tauri::command]
+pub async fn delete_rule(index: usize) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    let rules = &mut config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?
+        .routing
+        .rules;
+    check_index(rules.len(), index)?;
+    rules.remove(index);
+    info!("Routing rule {} deleted", index);
+    apply_rules(&mut config).await
+}
+
+/// Move routing rule, rules are matched in order
+///
+/// ## Arguments
+///
+/// `from`: current index of rule
+/// `to`: target index of rule
+#[tauri::command]
+pub async fn move_rule(from: usize, to: usize) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    let rules = &mut config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?
+        .routing
+        .rules;
+    check_index(rules.len(), from)?;
+    check_index(rules.len(), to)?;
+    let rule = rules.remove(from);
+    rules.insert(to, rule);
+    info!("Routing rule moved from {} to {}", from, to);
+    apply_rules(&mut config).await
+}
+
+/// Enable or disable routing rule, disabled rule is not passed to core
+#[tauri::command]
+pub async fn set_rule_enabled(index: usize, enabled: bool) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    let rules = &mut config
+        .core
+        .as_mut()
+        .ok_or(anyhow!("cannot found core config"))?
+        .routing
+        .rules;
+    check_index(rules.len(), index)?;
+    rules[index].disabled = !enabled;
+    info!(
+        "Routing rule {} {}",
+        index,
+        if enabled { "enabled" } else { "disabled" }
+    );
+    apply_rules(&mut config).await
+}
+
+/// Find which rule and outbound the destination would use,
+/// rules of routing mode, rule sets and overlay are included
+#[tauri::command]
+pub async fn simulate_route(request: RouteRequest) -> VResult<RouteResult> {
+    let runtime = CONFIG.lock().await.runtime_core()?;
+    Ok(simulate::simulate_route(&runtime, &request).await?)
+}
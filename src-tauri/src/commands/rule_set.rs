@@ -0,0 +1,210 @@
+use crate::{
+    config::{
+        routing::RouteTargets,
+        rule_set::{
+            download_rule_set, remove_entries, save_entries, validate_rule_set, RuleSetEntries,
+        },
+        RuleSet, VConfig,
+    },
+    message::{ConfigMsg, MSG_TX},
+    utils::error::VResult,
+    CONFIG,
+};
+use anyhow::{anyhow, Result};
+use log::{error, info};
+
+/// Downloaded entries of rule sets by name
+pub type RuleSetDownloads = Vec<(String, Result<RuleSetEntries>)>;
+
+/// Download all enabled rule sets, config lock is not needed
+///
+/// ## Arguments
+///
+/// `rule_sets`: rule sets in rua config
+pub async fn download_rule_sets(rule_sets: &[RuleSet]) -> RuleSetDownloads {
+    let mut downloads = vec![];
+    for rule_set in rule_sets.iter().filter(|r| !r.disabled) {
+        downloads.push((rule_set.name.clone(), download_rule_set(rule_set).await));
+    }
+    downloads
+}
+
+/// Save downloaded rule sets, errors are recorded in each rule set
+///
+/// ## Return
+///
+/// Whether any rule set is changed, core need to be restarted if so
+pub fn save_rule_sets(config: &mut VConfig, downloads: RuleSetDownloads) -> VResult<bool> {
+    let dir = config.rule_set_dir();
+    let mut changed = false;
+    for (name, entries) in downloads {
+        // rule set may be removed while downloading
+        let rule_set = match config.rua.rule_sets.iter_mut().find(|r| r.name == name) {
+            Some(rule_set) => rule_set,
+            None => continue,
+        };
+        match entries.and_then(|entries| save_entries(&dir, rule_set, &entries)) {
+            Ok(c) => changed |= c,
+            Err(err) => {
+                error!("Update rule set {} failed {}", rule_set.name, err);
+                rule_set.error = Some(err.to_string());
+            }
+        }
+    }
+    config.write_rua()?;
+    if changed {
+        config.write_runtime()?;
+    }
+    Ok(changed)
+}
+
+/// Check rule set and its outbound
+fn check_rule_set(config: &VConfig, rule_set: &RuleSet) -> VResult<()> {
+    validate_rule_set(rule_set)?;
+    let targets = RouteTargets::from_runtime(&config.runtime_core()?);
+    if !targets.outbounds.contains(&rule_set.outbound_tag) {
+        return Err(anyhow!("outbound {} not found", rule_set.outbound_tag).into());
+    }
+    Ok(())
+}
+
+/// All rule sets with entry counts and last update time
+#[tauri::command]
+pub async fn get_rule_sets() -> VResult<Vec<RuleSet>> {
+    let config = CONFIG.lock().await;
+    Ok(config.rua.rule_sets.clone())
+}
+
+/// Add rule set, download it and restart core
+#[tauri::command]
+pub async fn add_rule_set(rule_set: RuleSet) -> VResult<RuleSet> {
+    let mut rule_set = rule_set;
+    let config = CONFIG.lock().await;
+    check_rule_set(&config, &rule_set)?;
+    drop(config);
+    let entries = download_rule_set(&rule_set).await?;
+
+    let mut config = CONFIG.lock().await;
+    if config.rua.rule_sets.iter().any(|r| r.name == rule_set.name) {
+        return Err(anyhow!("rule set {} already exists", rule_set.name).into());
+    }
+    save_entries(&config.rule_set_dir(), &mut rule_set, &entries)?;
+    config.rua.rule_sets.push(rule_set.clone());
+    config.write_rua()?;
+    config.write_runtime()?;
+    info!(
+        "Rule set {} added with {} domains and {} ips",
+        rule_set.name, rule_set.domain_count, rule_set.ip_count
+    );
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(rule_set)
+}
+
+/// Change rule set by name, download it again if url or format changed
+#[tauri::command]
+pub async fn edit_rule_set(name: String, rule_set: RuleSet) -> VResult<RuleSet> {
+    let config = CONFIG.lock().await;
+    check_rule_set(&config, &rule_set)?;
+    let old = config
+        .rua
+        .rule_sets
+        .iter()
+        .find(|r| r.name == name)
+        .ok_or(anyhow!("rule set {} not found", name))?;
+    let refetch = old.url != rule_set.url || old.format != rule_set.format;
+    let mut rule_set = RuleSet {
+        domain_count: old.domain_count,
+        ip_count: old.ip_count,
+        updated_at: old.updated_at.clone(),
+        error: old.error.clone(),
+        ..rule_set
+    };
+    drop(config);
+    let entries = if refetch || rule_set.name != name {
+        Some(download_rule_set(&rule_set).await?)
+    } else {
+        None
+    };
+
+    let mut config = CONFIG.lock().await;
+    let dir = config.rule_set_dir();
+    let index = config
+        .rua
+        .rule_sets
+        .iter()
+        .position(|r| r.name == name)
+        .ok_or(anyhow!("rule set {} not found", name))?;
+    if rule_set.name != name && config.rua.rule_sets.iter().any(|r| r.name == rule_set.name) {
+        return Err(anyhow!("rule set {} already exists", rule_set.name).into());
+    }
+    if let Some(entries) = entries {
+        save_entries(&dir, &mut rule_set, &entries)?;
+        if rule_set.name != name {
+            remove_entries(&dir, &name)?;
+        }
+    }
+    config.rua.rule_sets[index] = rule_set.clone();
+    config.write_rua()?;
+    config.write_runtime()?;
+    info!("Rule set {} updated", rule_set.name);
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(rule_set)
+}
+
+/// Remove rule set and its entries
+#[tauri::command]
+pub async fn remove_rule_set(name: String) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    let index = config
+        .rua
+        .rule_sets
+        .iter()
+        .position(|r| r.name == name)
+        .ok_or(anyhow!("rule set {} not found", name))?;
+    config.rua.rule_sets.remove(index);
+    remove_entries(&config.rule_set_dir(), &name)?;
+    config.write_rua()?;
+    config.write_runtime()?;
+    info!("Rule set {} removed", name);
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
+
+/// Download rule sets again
+///
+/// ## Arguments
+///
+/// `name`: rule set name, update all enabled rule sets if it's None
+#[tauri::command]
+pub async fn update_rule_sets(name: Option<String>) -> VResult<Vec<RuleSet>> {
+    let rule_sets = CONFIG.lock().await.rua.rule_sets.clone();
+    let changed = match name {
+        Some(name) => {
+            let rule_set = rule_sets
+                .iter()
+                .find(|r| r.name == name)
+                .ok_or(anyhow!("rule set {} not found", name))?;
+            let entries = download_rule_set(rule_set).await?;
+            let mut config = CONFIG.lock().await;
+            let dir = config.rule_set_dir();
+            let rule_set = config
+                .rua
+                .rule_sets
+                .iter_mut()
+                .find(|r| r.name == name)
+                .ok_or(anyhow!("rule set {} not found", name))?;
+            let changed = save_entries(&dir, rule_set, &entries)?;
+            config.write_rua()?;
+            config.write_runtime()?;
+            changed
+        }
+        None => {
+            let downloads = download_rule_sets(&rule_sets).await;
+            save_rule_sets(&mut *CONFIG.lock().await, downloads)?
+        }
+    };
+    if changed {
+        MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    }
+    Ok(CONFIG.lock().await.rua.rule_sets.clone())
+}
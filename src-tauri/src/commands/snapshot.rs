@@ -0,0 +1,67 @@
+use crate::{
+    config::snapshot::{self, diff_lines, list_snapshots, snapshot_target, DiffLine, Snapshot},
+    message::{ConfigMsg, MSG_TX},
+    utils::{error::VResult, file::atomic_write},
+    CONFIG,
+};
+use anyhow::anyhow;
+use log::{error, info};
+use std::fs;
+
+/// List snapshots of `config.json` and `config.toml`, newest first
+#[tauri::command]
+pub async fn get_snapshots() -> VResult<Vec<Snapshot>> {
+    let config = CONFIG.lock().await;
+    let snapshots = list_snapshots(
+        &config.snapshot_dir(),
+        &[&config.core_path, &config.rua_path],
+    )?;
+    Ok(snapshots)
+}
+
+/// Diff from snapshot to current config file
+///
+/// ## Arguments
+///
+/// `name`: snapshot name
+#[tauri::command]
+pub async fn diff_snapshot(name: String) -> VResult<Vec<DiffLine>> {
+    let config = CONFIG.lock().await;
+    let (snapshot, target) = snapshot_target(
+        &config.snapshot_dir(),
+        &name,
+        &[&config.core_path, &config.rua_path],
+    )?;
+    let old = fs::read_to_string(snapshot)?;
+    let new = fs::read_to_string(target).unwrap_or_default();
+    Ok(diff_lines(&old, &new)?)
+}
+
+/// Restore config file from snapshot and restart core.
+/// If the snapshot cannot be loaded, the config file will be reverted.
+///
+/// ## Arguments
+///
+/// `name`: snapshot name
+#[tauri::command]
+pub async fn restore_snapshot(name: String) -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    let dir = config.snapshot_dir();
+    let core_path = config.core_path.clone();
+    let rua_path = config.rua_path.clone();
+    let (snapshot, target) = snapshot_target(&dir, &name, &[&core_path, &rua_path])?;
+    let current = fs::read(target)?;
+    snapshot::restore_snapshot(&dir, &snapshot, target)?;
+    if let Err(err) = config.reload() {
+        error!("Load snapshot {} failed {}, revert it", name, err);
+        atomic_write(target, &current)?;
+        config.reload()?;
+        return Err(anyhow!("load snapshot {} failed {}", name, err).into());
+    }
+    config.write_runtime()?;
+    info!("Config restored from snapshot {}", name);
+    drop(config);
+    MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+    MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+    Ok(())
+}
@@ -1,7 +1,8 @@
 use std::time::Duration;
 
 use crate::{
-    config::{Node, SubsAutoUpdate, Subscription, VConfig},
+    commands::rule_set::{download_rule_sets, save_rule_sets},
+    config::{assign_node_ids, Node, SubsAutoUpdate, Subscription, VConfig},
     message::MSG_TX,
     utils::{
         consts::{NAME, VERSION},
@@ -111,11 +112,10 @@ async fn request_subs(name: &str, url: &str) -> VResult<Vec<Node>> {
     let subscription = general_purpose::STANDARD.decode(result)?;
     let subscription = String::from_utf8_lossy(&subscription).to_string();
     // Serizlize outbound nodes to json
-    let subscription = subscription
+    let mut subscription = subscription
         .split('\n')
         .filter(|line| !line.is_empty())
-        .enumerate()
-        .map(|(index, line)| {
+        .map(|line| {
             let (node_type, link) = line
                 .split_once("://")
                 .ok_or(anyhow!("Cannot serialize node link"))?;
@@ -124,14 +124,13 @@ async fn request_subs(name: &str, url: &str) -> VResult<Vec<Node>> {
             let mut node = serde_json::from_str::<Node>(&link)?;
 
             node.subs = Some(name.to_string());
-            // Add unique id
-            let id = md5::compute(format!("{}-{}-{}-{}", node.ps, node.add, node.port, index));
-            node.node_id = Some(format!("{:?}", id));
             node.raw_link = Some(line.to_owned());
             node.node_type = Some(NodeType::from(node_type));
             Ok(node)
         })
         .collect::<VResult<Vec<_>>>()?;
+    // Add unique id
+    assign_node_ids(name, &mut subscription);
     debug!("{subscription:?}");
     Ok(subscription)
 }
@@ -167,16 +166,10 @@ pub async fn update_all_subs_core(config: &mut VConfig) -> VResult<()> {
     Ok(())
 }
 
+/// Start or stop the update timer by settings,
+/// subscriptions are updated on startup by `startup_subs_update`
 pub async fn check_subs_update(config: &mut VConfig) -> VResult<()> {
     match config.rua.settings.update_subs {
-        Some(SubsAutoUpdate::Startup) => {
-            update_all_subs_core(config).await?;
-            MSG_TX
-                .lock()
-                .await
-                .send(crate::message::ConfigMsg::RestartCore)
-                .await?;
-        }
         Some(SubsAutoUpdate::Time) => {
             let duration = config.rua.settings.update_time;
             timer_update(duration).await;
@@ -189,6 +182,43 @@ pub async fn check_subs_update(config: &mut VConfig) -> VResult<()> {
     Ok(())
 }
 
+/// Update subscriptions if it's set to update on startup, otherwise start the update timer
+pub async fn startup_subs_update() -> VResult<()> {
+    let mut config = CONFIG.lock().await;
+    if !matches!(
+        config.rua.settings.update_subs,
+        Some(SubsAutoUpdate::Startup)
+    ) {
+        return check_subs_update(&mut config).await;
+    }
+    drop(config);
+    update_subs_and_rule_sets().await?;
+    MSG_TX
+        .lock()
+        .await
+        .send(crate::message::ConfigMsg::RestartCore)
+        .await?;
+    Ok(())
+}
+
+/// Update all subscriptions and rule sets,
+/// rule sets are downloaded without holding config lock
+///
+/// ## Return
+///
+/// Whether any rule set is changed
+async fn update_subs_and_rule_sets() -> VResult<bool> {
+    let mut config = CONFIG.lock().await;
+    let _ = update_all_subs_core(&mut config)
+        .await
+        .map_err(|e| error!("auto update subs failed {}", e));
+    let rule_sets = config.rua.rule_sets.clone();
+    drop(config);
+    let downloads = download_rule_sets(&rule_sets).await;
+    let mut config = CONFIG.lock().await;
+    save_rule_sets(&mut config, downloads)
+}
+
 pub async fn timer_update(duration: Option<u16>) {
     let mut timer = UPDATE_TIMER.lock().await;
     if let Some(duration) = duration {
@@ -196,10 +226,18 @@ pub async fn timer_update(duration: Option<u16>) {
         timer.duration = Duration::from_secs((duration * 60).into());
         timer.job = || {
             async_runtime::spawn(async move {
-                let mut config = CONFIG.lock().await;
-                let _ = update_all_subs_core(&mut config)
+                // rules only take effect after core restarted
+                if let Ok(true) = update_subs_and_rule_sets()
                     .await
-                    .map_err(|e| error!("auto update subs failed {}", e));
+                    .map_err(|e| error!("auto update rule sets failed {}", e))
+                {
+                    let _ = MSG_TX
+                        .lock()
+                        .await
+                        .send(crate::message::ConfigMsg::RestartCore)
+                        .await
+                        .map_err(|e| error!("restart core failed {}", e));
+                }
             });
         };
         let _ = timer
@@ -0,0 +1,140 @@
+use super::{
+    node_override::node_outbound, BalanceStrategy, BalancerGroup, BalancerStrategy, Balancers,
+    CoreConfig, Node, Observatory, ProxyMode, RConfig, Rule,
+};
+use anyhow::{anyhow, bail, Result};
+use log::warn;
+use url::Url;
+
+/// Balancer tag used by rules which go to `proxy`
+pub const BALANCER_TAG: &str = "proxy-balancer";
+/// Tag prefix of outbounds generated from group nodes,
+/// used as balancer selector and observatory subject.
+/// Reserved, user outbounds can not use it.
+pub const OUTBOUND_PREFIX: &str = "venus-balance-";
+
+/// Outbounds added by user can not use tags of generated outbounds
+pub fn check_reserved_tag(tag: &str) -> Result<()> {
+    if tag.starts_with(OUTBOUND_PREFIX) {
+        bail!(
+            "outbound tag {} is reserved, tags starting with {} are used by balancer",
+            tag,
+            OUTBOUND_PREFIX
+        );
+    }
+    Ok(())
+}
+
+/// Validate balancer group
+pub fn validate_group(group: &BalancerGroup) -> Result<()> {
+    if group.nodes.is_empty() && group.subscription.is_none() {
+        bail!("no nodes selected for balancer");
+    }
+    if group.strategy == BalanceStrategy::LeastPing {
+        Url::parse(&group.probe_url)
+            .map_err(|e| anyhow!("invalid probe url {} {}", group.probe_url, e))?;
+        let interval = &group.probe_interval;
+        let number = interval.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        let unit = &interval[number.len()..];
+        if number.parse::<u32>().is_err() || !matches!(unit, "ms" | "s" | "m" | "h") {
+            bail!(
+                "invalid probe interval {}, expected like 30s or 1m",
+                interval
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Nodes in balancer group, selected nodes first then subscription nodes,
+/// duplicated nodes are removed
+pub fn group_nodes<'a>(group: &BalancerGroup, rua: &'a RConfig) -> Vec<&'a Node> {
+    let mut nodes: Vec<&Node> = vec![];
+    let all = rua.subscriptions.iter().flat_map(|sub| sub.nodes.iter());
+    for id in &group.nodes {
+        if let Some(node) = all.clone().find(|n| n.node_id.as_ref() == Some(id)) {
+            nodes.push(node);
+        }
+    }
+    if let Some(name) = &group.subscription {
+        let filter = group.filter.as_deref().unwrap_or_default().to_lowercase();
+        rua.subscriptions
+            .iter()
+            .filter(|sub| &sub.name == name)
+            .flat_map(|sub| sub.nodes.iter())
+            .filter(|n| n.ps.to_lowercase().contains(&filter))
+            .for_each(|node| {
+                if !nodes.iter().any(|n| n.node_id == node.node_id) {
+                    nodes.push(node);
+                }
+            });
+    }
+    nodes
+}
+
+/// Generate outbounds, balancer and observatory of enabled balancer group,
+/// rules to `proxy` go to the balancer instead.
+///
+/// ## Arguments
+///
+/// `core`: the runtime core config, routing mode rules are already applied
+/// `rua`: balancer group and subscription nodes
+pub fn apply_balancer(core: &mut CoreConfig, rua: &RConfig) {
+    let group = &rua.balancer;
+    if !group.enabled {
+        return;
+    }
+    let nodes = group_nodes(group, rua);
+    core.outbounds
+        .retain(|outbound| !outbound.tag.starts_with(OUTBOUND_PREFIX));
+    let mut count = 0;
+    for (index, node) in nodes.iter().enumerate() {
+        match node_outbound(node, rua, format!("{}{}", OUTBOUND_PREFIX, index)) {
+            Ok(outbound) => {
+                core.outbounds.push(outbound);
+                count += 1;
+            }
+            Err(e) => warn!("Skip balancer node {} {}", node.ps, e),
+        }
+    }
+    if count == 0 {
+        warn!("No available nodes in balancer group, use selected node");
+        return;
+    }
+
+    core.routing.balancers.retain(|b| b.tag != BALANCER_TAG);
+    core.routing.balancers.push(Balancers {
+        tag: BALANCER_TAG.into(),
+        selector: vec![OUTBOUND_PREFIX.into()],
+        strategy: Some(BalancerStrategy {
+            type_field: group.strategy.as_str().into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    if group.strategy == BalanceStrategy::LeastPing {
+        core.observatory = Some(Observatory {
+            subject_selector: vec![OUTBOUND_PREFIX.into()],
+            probe_url: group.probe_url.clone(),
+            probe_interval: group.probe_interval.clone(),
+            ..Default::default()
+        });
+    }
+
+    core.routing
+        .rules
+        .iter_mut()
+        .filter(|rule| rule.outbound_tag == "proxy")
+        .for_each(|rule| {
+            rule.outbound_tag = String::new();
+            rule.balancer_tag = Some(BALANCER_TAG.into());
+        });
+    // Traffic matches no rule goes to the first outbound, route it to balancer
+    if rua.mode == ProxyMode::Rule {
+        core.routing.rules.push(Rule {
+            network: Some("tcp,udp".into()),
+            balancer_tag: Some(BALANCER_TAG.into()),
+            ..Rule::new(String::new())
+        });
+    }
+}
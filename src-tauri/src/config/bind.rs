@@ -0,0 +1,107 @@
+use super::{
+    balancer::OUTBOUND_PREFIX, chain::is_hop_of, BindTarget, CoreConfig, Outbound, OutboundBind,
+};
+use anyhow::{anyhow, bail, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Network interface and its addresses
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NetInterface {
+    pub name: String,
+    pub addresses: Vec<String>,
+    pub loopback: bool,
+}
+
+/// Network interfaces of this machine, in the order of OS
+pub fn list_interfaces() -> Result<Vec<NetInterface>> {
+    let mut interfaces: Vec<NetInterface> = vec![];
+    for interface in if_addrs::get_if_addrs()? {
+        let address = interface.ip().to_string();
+        match interfaces.iter_mut().find(|i| i.name == interface.name) {
+            Some(found) => found.addresses.push(address),
+            None => interfaces.push(NetInterface {
+                loopback: interface.is_loopback(),
+                name: interface.name,
+                addresses: vec![address],
+            }),
+        }
+    }
+    Ok(interfaces)
+}
+
+/// Validate bind target, interface not found is allowed
+/// because VPN interfaces may be down for now
+fn validate_target(target: &BindTarget, interfaces: &[NetInterface]) -> Result<()> {
+    if let Some(interface) = &target.interface {
+        if interface.trim().is_empty() {
+            bail!("interface name is empty");
+        }
+        if !interfaces.iter().any(|i| &i.name == interface) {
+            warn!("Interface {} not found for now", interface);
+        }
+    }
+    if let Some(address) = &target.address {
+        let ip = address
+            .parse::<IpAddr>()
+            .map_err(|_| anyhow!("invalid source address {}", address))?;
+        if !interfaces
+            .iter()
+            .any(|i| i.addresses.iter().any(|a| a == address))
+        {
+            warn!("Source address {} not found on any interface for now", ip);
+        }
+    }
+    Ok(())
+}
+
+/// Validate direct and proxy bind targets
+pub fn validate_bind(bind: &OutboundBind) -> Result<()> {
+    let interfaces = list_interfaces()?;
+    validate_target(&bind.direct, &interfaces)?;
+    validate_target(&bind.proxy, &interfaces)?;
+    Ok(())
+}
+
+/// Outbounds generated from nodes, including chain hops, balancer and speed test
+fn is_proxy_outbound(tag: &str) -> bool {
+    ["proxy", "speed"]
+        .iter()
+        .any(|base| tag == *base || is_hop_of(tag, base))
+        || tag.starts_with(OUTBOUND_PREFIX)
+}
+
+fn bind_outbound(outbound: &mut Outbound, target: &BindTarget) {
+    if let Some(interface) = &target.interface {
+        let sockopt = outbound
+            .stream_settings
+            .get_or_insert_with(Default::default)
+            .sockopt
+            .get_or_insert_with(Default::default);
+        // interface of node override takes precedence
+        sockopt
+            .bind_to_device
+            .get_or_insert_with(|| interface.clone());
+    }
+    if let Some(address) = &target.address {
+        outbound.send_through.get_or_insert_with(|| address.clone());
+    }
+}
+
+/// Bind `direct` and proxy outbounds in runtime config
+///
+/// ## Arguments
+///
+/// `core`: the runtime core config, node outbounds are already generated
+/// `bind`: bind targets
+pub fn apply_bind(core: &mut CoreConfig, bind: &OutboundBind) {
+    for outbound in core.outbounds.iter_mut() {
+        if outbound.tag == "direct" {
+            bind_outbound(outbound, &bind.direct);
+        } else if is_proxy_outbound(&outbound.tag) {
+            bind_outbound(outbound, &bind.proxy);
+        }
+    }
+}
@@ -0,0 +1,492 @@
+use super::{snapshot::force_snapshot, CoreConfig, RConfig, VConfig};
+use crate::utils::{consts::VERSION, file::atomic_write};
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+use toml::Table;
+
+/// Bump it when bundle layout changed
+const BUNDLE_FORMAT: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+/// Single file in bundle should not be larger than this
+const MAX_ENTRY_SIZE: u64 = 16 * 1024 * 1024;
+/// Keys in core config which hold credentials
+const SECRET_KEYS: [&str; 5] = ["id", "password", "pass", "secretKey", "privateKey"];
+
+/// Entry name and content of files in bundle
+type BundleFiles = Vec<(String, Vec<u8>)>;
+
+/// Content of files before import, `None` if file not exist.
+/// Used to revert the import when imported config cannot be loaded.
+pub struct ImportBackup(Vec<(PathBuf, Option<Vec<u8>>)>);
+
+impl ImportBackup {
+    /// Write back all files replaced by import, and remove created files
+    pub fn restore(&self) -> Result<()> {
+        for (path, content) in &self.0 {
+            match content {
+                Some(content) => atomic_write(path, content)?,
+                None if path.exists() => fs::remove_file(path)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Describe what's in a bundle, stored as `manifest.json`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleManifest {
+    pub format: u32,
+    /// Venus version which exported the bundle
+    pub app_version: String,
+    pub created_at: String,
+    /// Subscription urls and credentials are removed
+    pub stripped: bool,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum BundleAction {
+    Create,
+    Replace,
+    Unchanged,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleChange {
+    pub file: String,
+    pub action: BundleAction,
+}
+
+/// What will happen when import the bundle
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundlePreview {
+    pub manifest: BundleManifest,
+    pub changes: Vec<BundleChange>,
+}
+
+/// Bundle entry name to local file path
+fn entry_path(config: &VConfig, name: &str) -> Result<PathBuf> {
+    let path = match name {
+        "config.toml" => config.rua_path.clone(),
+        "config.json" => config.core_path.clone(),
+        "overlay.json" => config.overlay_path.clone(),
+        _ => {
+            let (dir, file) = name
+                .split_once('/')
+                .ok_or(anyhow!("unexpected file {} in bundle", name))?;
+            let dir = match dir {
+                "profiles" => config.profile_dir(),
+                "rules" => config.rule_set_dir(),
+                _ => bail!("unexpected file {} in bundle", name),
+            };
+            if !file.ends_with(".json") || file.contains(['/', '\\']) || file == ".json" {
+                bail!("unexpected file {} in bundle", name);
+            }
+            dir.join(file)
+        }
+    };
+    Ok(path)
+}
+
+/// Json files in folder, with bundle entry name `{prefix}/{file}`
+fn folder_files(dir: &Path, prefix: &str) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let files = fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(".json") && !name.starts_with('.') {
+                Some((format!("{}/{}", prefix, name), entry.path()))
+            } else {
+                None
+            }
+        })
+        .collect();
+    Ok(files)
+}
+
+/// Collect files of current config
+fn collect_files(config: &VConfig, strip: bool) -> Result<BundleFiles> {
+    let mut files = vec![];
+
+    let mut rua = config.rua.clone();
+    if strip {
+        strip_rua(&mut rua);
+    }
+    files.push((
+        "config.toml".to_owned(),
+        toml::to_string(&rua)?.into_bytes(),
+    ));
+
+    let mut json_files = vec![
+        ("config.json".to_owned(), config.core_path.clone()),
+        ("overlay.json".to_owned(), config.overlay_path.clone()),
+    ];
+    json_files.append(&mut folder_files(&config.profile_dir(), "profiles")?);
+    for (name, path) in json_files {
+        if !path.exists() {
+            continue;
+        }
+        let content = if strip {
+            let mut value: Value = serde_json::from_slice(&fs::read(&path)?)?;
+            strip_secrets(&mut value);
+            serde_json::to_vec_pretty(&value)?
+        } else {
+            fs::read(&path)?
+        };
+        files.push((name, content));
+    }
+    // entries of rule sets have no credentials
+    for (name, path) in folder_files(&config.rule_set_dir(), "rules")? {
+        files.push((name, fs::read(&path)?));
+    }
+    Ok(files)
+}
+
+/// Remove subscription urls and node credentials
+fn strip_rua(rua: &mut RConfig) {
+    rua.subscriptions.iter_mut().for_each(|sub| {
+        sub.url = String::new();
+        sub.nodes.iter_mut().for_each(|node| {
+            node.id = String::new();
+            node.raw_link = None;
+        });
+    });
+}
+
+/// Blank all credential fields in json
+fn strip_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => map.iter_mut().for_each(|(key, value)| {
+            if SECRET_KEYS.contains(&key.as_str()) && value.is_string() {
+                *value = Value::String(String::new());
+            } else {
+                strip_secrets(value);
+            }
+        }),
+        Value::Array(list) => list.iter_mut().for_each(strip_secrets),
+        _ => {}
+    }
+}
+
+/// Fill subscription urls and node credentials removed by stripped export
+/// with current config, subscriptions are matched by name and nodes by node id.
+fn restore_rua(rua: &mut Table, current: &Table) {
+    let find = |list: Option<&toml::Value>, key: &str, value: Option<&toml::Value>| {
+        list.and_then(|l| l.as_array())
+            .and_then(|l| {
+                l.iter()
+                    .find(|item| value.is_some() && item.get(key) == value)
+            })
+            .and_then(|item| item.as_table())
+            .cloned()
+    };
+    let subscriptions = match rua.get_mut("subscriptions").and_then(|s| s.as_array_mut()) {
+        Some(subscriptions) => subscriptions,
+        None => return,
+    };
+    for sub in subscriptions.iter_mut().filter_map(|s| s.as_table_mut()) {
+        let current_sub = match find(current.get("subscriptions"), "name", sub.get("name")) {
+            Some(current_sub) => current_sub,
+            None => continue,
+        };
+        fill_empty(sub, &current_sub, "url");
+        let nodes = match sub.get_mut("nodes").and_then(|n| n.as_array_mut()) {
+            Some(nodes) => nodes,
+            None => continue,
+        };
+        for node in nodes.iter_mut().filter_map(|n| n.as_table_mut()) {
+            if let Some(current_node) = find(current_sub.get("nodes"), "nodeId", node.get("nodeId"))
+            {
+                fill_empty(node, &current_node, "id");
+                fill_empty(node, &current_node, "rawLink");
+            }
+        }
+    }
+}
+
+/// Copy `key` from `current` when it's missing or empty in `target`
+fn fill_empty(target: &mut Table, current: &Table, key: &str) {
+    let empty = target
+        .get(key)
+        .map(|v| v.as_str() == Some(""))
+        .unwrap_or(true);
+    if let (true, Some(value)) = (empty, current.get(key)) {
+        target.insert(key.to_owned(), value.clone());
+    }
+}
+
+/// Fill blanked credential fields with current json,
+/// items in array are matched by `tag`, or by index if they have no tag.
+fn restore_secrets(value: &mut Value, current: &Value) {
+    match (value, current) {
+        (Value::Object(map), Value::Object(current)) => map.iter_mut().for_each(|(key, value)| {
+            let current = match current.get(key) {
+                Some(current) => current,
+                None => return,
+            };
+            if SECRET_KEYS.contains(&key.as_str()) && value.as_str() == Some("") {
+                *value = current.clone();
+            } else {
+                restore_secrets(value, current);
+            }
+        }),
+        (Value::Array(list), Value::Array(current)) => {
+            list.iter_mut().enumerate().for_each(|(i, value)| {
+                let current = match value.get("tag") {
+                    Some(tag) => current.iter().find(|c| c.get("tag") == Some(tag)),
+                    None => current.get(i),
+                };
+                if let Some(current) = current {
+                    restore_secrets(value, current);
+                }
+            })
+        }
+        _ => {}
+    }
+}
+
+/// Merge stripped bundle files with credentials of current config
+fn restore_files(config: &VConfig, files: &mut BundleFiles) -> Result<()> {
+    for (name, content) in files.iter_mut() {
+        if name == "config.toml" {
+            let mut rua = toml::from_str::<Table>(std::str::from_utf8(content)?)?;
+            restore_rua(&mut rua, &Table::try_from(&config.rua)?);
+            *content = toml::to_string(&rua)?.into_bytes();
+        } else if !name.starts_with("rules/") {
+            let current = match fs::read(entry_path(config, name)?) {
+                Ok(current) => current,
+                Err(_) => continue,
+            };
+            let current = match serde_json::from_slice::<Value>(&current) {
+                Ok(current) => current,
+                Err(_) => continue,
+            };
+            let mut value: Value = serde_json::from_slice(content)?;
+            restore_secrets(&mut value, &current);
+            *content = serde_json::to_vec_pretty(&value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Export all config files to a `.tar.gz` bundle
+///
+/// ## Arguments
+///
+/// `config`: current config
+/// `target`: bundle file path
+/// `strip`: remove subscription urls and credentials
+pub fn export_bundle(config: &VConfig, target: &Path, strip: bool) -> Result<BundleManifest> {
+    let files = collect_files(config, strip)?;
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        app_version: VERSION.to_owned(),
+        created_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        stripped: strip,
+        files: files.iter().map(|(name, _)| name.clone()).collect(),
+    };
+    let manifest_content = serde_json::to_vec_pretty(&manifest)?;
+
+    let encoder = GzEncoder::new(vec![], Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    let mtime = Local::now().timestamp().max(0) as u64;
+    let entries = [(MANIFEST.to_owned(), manifest_content)]
+        .into_iter()
+        .chain(files);
+    for (name, content) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, &name, content.as_slice())?;
+    }
+    let bundle = builder.into_inner()?.finish()?;
+    atomic_write(target, &bundle)?;
+    Ok(manifest)
+}
+
+/// Read and validate all files in bundle
+fn read_bundle(source: &Path) -> Result<(BundleManifest, BundleFiles)> {
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(source)?));
+    let mut manifest = None;
+    let mut files = vec![];
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().replace('\\', "/");
+        if entry.size() > MAX_ENTRY_SIZE {
+            bail!("file {} in bundle is too large", name);
+        }
+        let mut content = vec![];
+        entry.take(MAX_ENTRY_SIZE).read_to_end(&mut content)?;
+        if name == MANIFEST {
+            manifest = Some(serde_json::from_slice::<BundleManifest>(&content)?);
+        } else {
+            files.push((name, content));
+        }
+    }
+    let manifest = manifest.ok_or(anyhow!("bundle manifest not found"))?;
+    if manifest.format > BUNDLE_FORMAT {
+        bail!(
+            "bundle format {} is not supported, please upgrade Venus to {} or newer",
+            manifest.format,
+            manifest.app_version
+        );
+    }
+    for (name, content) in &files {
+        match name.as_str() {
+            "config.toml" => {
+                toml::from_str::<toml::Table>(std::str::from_utf8(content)?)
+                    .map_err(|e| anyhow!("invalid config.toml in bundle {}", e))?;
+            }
+            "config.json" => {
+                serde_json::from_slice::<CoreConfig>(content)
+                    .map_err(|e| anyhow!("invalid config.json in bundle {}", e))?;
+            }
+            _ => {
+                serde_json::from_slice::<Value>(content)
+                    .map_err(|e| anyhow!("invalid {} in bundle {}", name, e))?;
+            }
+        }
+    }
+    Ok((manifest, files))
+}
+
+/// Read bundle, credentials of stripped bundle are filled with current config
+fn load_bundle(config: &VConfig, source: &Path) -> Result<(BundleManifest, BundleFiles)> {
+    let (manifest, mut files) = read_bundle(source)?;
+    if manifest.stripped {
+        restore_files(config, &mut files)?;
+    }
+    Ok((manifest, files))
+}
+
+/// Show which files will be created or replaced by bundle
+pub fn preview_bundle(config: &VConfig, source: &Path) -> Result<BundlePreview> {
+    let (manifest, files) = load_bundle(config, source)?;
+    let changes = files
+        .iter()
+        .map(|(name, content)| {
+            let path = entry_path(config, name)?;
+            let action = match fs::read(&path) {
+                Ok(current) if &current == content => BundleAction::Unchanged,
+                Ok(_) => BundleAction::Replace,
+                Err(_) => BundleAction::Create,
+            };
+            Ok(BundleChange {
+                file: name.clone(),
+                action,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(BundlePreview { manifest, changes })
+}
+
+/// Write all files in bundle to config folder,
+/// config need to be reloaded after import.
+///
+/// ## Return
+///
+/// Bundle manifest, and the replaced files which can be used to revert the import
+pub fn import_bundle(config: &VConfig, source: &Path) -> Result<(BundleManifest, ImportBackup)> {
+    let (manifest, files) = load_bundle(config, source)?;
+    let targets = files
+        .iter()
+        .map(|(name, _)| entry_path(config, name))
+        .collect::<Result<Vec<_>>>()?;
+    // snapshots are kept even if import succeed, so it can be undone later
+    for path in [&config.rua_path, &config.core_path] {
+        force_snapshot(&config.snapshot_dir(), path)?;
+    }
+    let backup = ImportBackup(
+        targets
+            .iter()
+            .map(|path| (path.clone(), fs::read(path).ok()))
+            .collect(),
+    );
+    for ((_, content), path) in files.iter().zip(&targets) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Err(err) = atomic_write(path, content) {
+            error!("Write {} failed {}, revert import", path.display(), err);
+            backup.restore()?;
+            return Err(err);
+        }
+    }
+    info!("{} files imported", targets.len());
+    Ok((manifest, backup))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn restore_stripped_secrets() {
+        let current = json!({
+            "outbounds": [
+                { "tag": "direct", "protocol": "freedom" },
+                { "tag": "proxy", "settings": { "vnext": [{ "users": [{ "id": "uuid" }] }] } }
+            ]
+        });
+        let mut stripped = current.clone();
+        strip_secrets(&mut stripped);
+        // order of outbounds changed in bundle
+        stripped["outbounds"].as_array_mut().unwrap().reverse();
+        restore_secrets(&mut stripped, &current);
+        assert_eq!(
+            stripped["outbounds"][0]["settings"]["vnext"][0]["users"][0]["id"],
+            "uuid"
+        );
+    }
+
+    #[test]
+    fn restore_stripped_subscriptions() {
+        let current: Table = toml::from_str(
+            r#"
+            [[subscriptions]]
+            name = "default"
+            url = "https://example.com/subscribe"
+            [[subscriptions.nodes]]
+            id = "uuid"
+            nodeId = "node"
+            rawLink = "vmess://link"
+            "#,
+        )
+        .unwrap();
+        let mut stripped: Table = toml::from_str(
+            r#"
+            [[subscriptions]]
+            name = "default"
+            url = ""
+            [[subscriptions.nodes]]
+            id = ""
+            nodeId = "node"
+            "#,
+        )
+        .unwrap();
+        restore_rua(&mut stripped, &current);
+        assert_eq!(stripped, current);
+    }
+}
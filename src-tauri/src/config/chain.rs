@@ -0,0 +1,168 @@
+use super::{
+    find_node, node_override::node_outbound, CoreConfig, Node, Outbound, ProxyChain, ProxySetting,
+    RConfig,
+};
+use anyhow::{anyhow, bail, Result};
+
+/// Chains are selected and measured like nodes with id `chain:{name}`
+pub const CHAIN_PREFIX: &str = "chain:";
+
+/// Id used to select chain like a node
+pub fn chain_id(name: &str) -> String {
+    format!("{}{}", CHAIN_PREFIX, name)
+}
+
+fn hop_prefix(tag: &str) -> String {
+    format!("{}-hop-", tag)
+}
+
+/// Tag of the hop before exit node, the exit node uses the tag itself
+fn hop_tag(tag: &str, index: usize) -> String {
+    format!("{}{}", hop_prefix(tag), index)
+}
+
+/// Whether outbound is a chain hop of outbound `tag`
+pub fn is_hop_of(outbound_tag: &str, tag: &str) -> bool {
+    outbound_tag.starts_with(&hop_prefix(tag))
+}
+
+/// Validate chain name and nodes
+pub fn validate_chain(chain: &ProxyChain, rua: &RConfig) -> Result<()> {
+    let name = chain.name.trim();
+    if name.is_empty() {
+        bail!("chain name is empty");
+    }
+    if name.contains(':') {
+        bail!("chain name {} can not contain ':'", name);
+    }
+    if chain.nodes.len() < 2 {
+        bail!("chain {} needs at least two nodes", name);
+    }
+    for id in &chain.nodes {
+        find_node(id, rua)?;
+    }
+    Ok(())
+}
+
+pub fn find_chain<'a>(name: &str, rua: &'a RConfig) -> Result<&'a ProxyChain> {
+    rua.chains
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or(anyhow!("chain {} not found", name))
+}
+
+/// Build outbounds of chain, the last node is the exit with `tag`,
+/// each node dials through the previous one with `proxySettings`.
+///
+/// ## Arguments
+///
+/// `chain`: the chain
+/// `rua`: subscriptions of chain nodes
+/// `tag`: outbound tag of exit node
+pub fn chain_outbounds(chain: &ProxyChain, rua: &RConfig, tag: &str) -> Result<Vec<Outbound>> {
+    let nodes = chain
+        .nodes
+        .iter()
+        .map(|id| find_node(id, rua))
+        .collect::<Result<Vec<&Node>>>()?;
+    let last = nodes.len().saturating_sub(1);
+    let mut outbounds = vec![];
+    for (index, node) in nodes.iter().enumerate() {
+        let current = if index == last {
+            tag.to_owned()
+        } else {
+            hop_tag(tag, index)
+        };
+        let mut outbound = node_outbound(node, rua, current)?;
+        if index > 0 {
+            // without transport layer, TLS and transport of this hop are ignored by core
+            outbound.proxy_setting = Some(ProxySetting {
+                tag: hop_tag(tag, index - 1),
+                transport_layer: Some(true),
+                ..Default::default()
+            });
+        }
+        outbounds.push(outbound);
+    }
+    // Exit node first, it replaces the single node outbound
+    outbounds.rotate_right(1);
+    Ok(outbounds)
+}
+
+/// Build outbounds of node or chain id
+///
+/// ## Arguments
+///
+/// `id`: node id or chain id
+/// `rua`: subscriptions and chains
+/// `tag`: outbound tag of the node or exit node of chain
+pub fn outbounds_by_id(id: &str, rua: &RConfig, tag: &str) -> Result<Vec<Outbound>> {
+    match id.strip_prefix(CHAIN_PREFIX) {
+        Some(name) => chain_outbounds(find_chain(name, rua)?, rua, tag),
+        None => Ok(vec![node_outbound(find_node(id, rua)?, rua, tag.into())?]),
+    }
+}
+
+/// Replace outbound of tag and its chain hops,
+/// the first outbound is the one with tag, the others are hops.
+/// New `proxy` outbound is inserted at first, so it's the default outbound.
+pub fn replace_outbounds(core: &mut CoreConfig, tag: &str, outbounds: Vec<Outbound>) {
+    core.outbounds
+        .retain(|outbound| !is_hop_of(&outbound.tag, tag));
+    let mut outbounds = outbounds.into_iter();
+    if let Some(outbound) = outbounds.next() {
+        match core.outbounds.iter().position(|o| o.tag == tag) {
+            Some(index) => core.outbounds[index] = outbound,
+            None if tag == "proxy" => core.outbounds.insert(0, outbound),
+            None => core.outbounds.push(outbound),
+        }
+    }
+    core.outbounds.extend(outbounds);
+}
+
+/// Node or chain which speed test results are recorded to
+pub enum Measured<'a> {
+    Node(&'a mut Node),
+    Chain(&'a mut ProxyChain),
+}
+
+impl Measured<'_> {
+    pub fn set_delay(&mut self, delay: u64) {
+        match self {
+            Measured::Node(node) => node.delay = Some(delay),
+            Measured::Chain(chain) => chain.delay = Some(delay),
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        match self {
+            Measured::Node(node) => node.speed = Some(speed),
+            Measured::Chain(chain) => chain.speed = Some(speed),
+        }
+    }
+
+    pub fn set_connectivity(&mut self, connectivity: bool) {
+        match self {
+            Measured::Node(node) => node.connectivity = Some(connectivity),
+            Measured::Chain(chain) => chain.connectivity = Some(connectivity),
+        }
+    }
+}
+
+/// Find node or chain by id for recording speed test results
+pub fn find_measured<'a>(id: &str, rua: &'a mut RConfig) -> Result<Measured<'a>> {
+    if let Some(name) = id.strip_prefix(CHAIN_PREFIX) {
+        return rua
+            .chains
+            .iter_mut()
+            .find(|c| c.name == name)
+            .map(Measured::Chain)
+            .ok_or(anyhow!("chain {} not found", name));
+    }
+    rua.subscriptions
+        .iter_mut()
+        .flat_map(|sub| sub.nodes.iter_mut())
+        .find(|n| n.node_id.as_deref() == Some(id))
+        .map(Measured::Node)
+        .ok_or(anyhow!("node {} not found", id))
+}
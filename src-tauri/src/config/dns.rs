@@ -0,0 +1,177 @@
+use super::{
+    matcher::{validate_domain, validate_ip},
+    Dns, DnsServer, HostAddress,
+};
+use anyhow::{anyhow, bail, Result};
+use std::{collections::BTreeMap, net::IpAddr, path::PathBuf};
+use url::Url;
+
+/// Schemes of DoH, DoT, DoQ and TCP DNS server
+const DNS_SCHEMES: [&str; 8] = [
+    "https",
+    "https+local",
+    "tls",
+    "tls+local",
+    "quic",
+    "quic+local",
+    "tcp",
+    "tcp+local",
+];
+const QUERY_STRATEGIES: [&str; 3] = ["UseIP", "UseIPv4", "UseIPv6"];
+
+/// System hosts file path
+pub fn system_hosts_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        PathBuf::from(r"C:\Windows\System32\drivers\etc\hosts")
+    } else {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+/// Validate DNS server address
+pub fn validate_server_address(address: &str) -> Result<()> {
+    match address {
+        "localhost" | "fakedns" => return Ok(()),
+        "" => bail!("dns server address is empty"),
+        _ => {}
+    }
+    if address.contains("://") {
+        let url = Url::parse(address).map_err(|e| anyhow!("invalid dns url {} {}", address, e))?;
+        if !DNS_SCHEMES.contains(&url.scheme()) {
+            bail!(
+                "unsupported dns scheme {} in {}, expect one of {}",
+                url.scheme(),
+                address,
+                DNS_SCHEMES.join(", ")
+            );
+        }
+        if url.host_str().map(|h| h.is_empty()).unwrap_or(true) {
+            bail!("dns url {} has no host", address);
+        }
+        return Ok(());
+    }
+    address
+        .parse::<IpAddr>()
+        .map_err(|_| anyhow!("invalid dns server address {}", address))?;
+    Ok(())
+}
+
+fn validate_query_strategy(strategy: &Option<String>) -> Result<()> {
+    match strategy {
+        Some(s) if !QUERY_STRATEGIES.contains(&s.as_str()) => Err(anyhow!(
+            "invalid query strategy {}, expect one of {}",
+            s,
+            QUERY_STRATEGIES.join(", ")
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn validate_client_ip(ip: &Option<String>) -> Result<()> {
+    if let Some(ip) = ip {
+        ip.parse::<IpAddr>()
+            .map_err(|_| anyhow!("invalid client ip {}", ip))?;
+    }
+    Ok(())
+}
+
+/// Validate DNS servers
+///
+/// ## Return
+///
+/// All problems found in servers
+pub fn validate_servers(servers: &[DnsServer]) -> Vec<String> {
+    let mut errors = vec![];
+    let mut push = |result: Result<()>| {
+        if let Err(err) = result {
+            errors.push(err.to_string());
+        }
+    };
+    for server in servers {
+        push(validate_server_address(server.address()));
+        if let DnsServer::Server(server) = server {
+            server.domains.iter().for_each(|d| push(validate_domain(d)));
+            server
+                .expect_ips
+                .iter()
+                .for_each(|ip| push(validate_ip(ip)));
+            push(validate_client_ip(&server.client_ip));
+            push(validate_query_strategy(&server.query_strategy));
+        }
+    }
+    errors
+}
+
+/// Validate DNS hosts
+///
+/// ## Return
+///
+/// All problems found in hosts
+pub fn validate_hosts(hosts: &BTreeMap<String, HostAddress>) -> Vec<String> {
+    let mut errors = vec![];
+    for (domain, address) in hosts {
+        if let Err(err) = validate_domain(domain) {
+            errors.push(err.to_string());
+        }
+        let addresses = address.addresses();
+        if addresses.is_empty() {
+            errors.push(format!("address of host {} is empty", domain));
+        }
+        addresses
+            .iter()
+            .filter(|a| a.is_empty() || a.contains(char::is_whitespace))
+            .for_each(|a| errors.push(format!("invalid address {} of host {}", a, domain)));
+    }
+    errors
+}
+
+/// Validate whole DNS config
+pub fn validate_dns(dns: &Dns) -> Vec<String> {
+    let mut errors = validate_servers(&dns.servers);
+    errors.extend(validate_hosts(&dns.hosts));
+    if let Err(err) = validate_client_ip(&dns.client_ip) {
+        errors.push(err.to_string());
+    }
+    if let Err(err) = validate_query_strategy(&dns.query_strategy) {
+        errors.push(err.to_string());
+    }
+    errors
+}
+
+/// Parse hosts file content to DNS hosts,
+/// names without dot like `localhost` are skipped.
+///
+/// ## Arguments
+///
+/// `content`: hosts file content, `IP name [name...] # comment`
+pub fn parse_hosts(content: &str) -> BTreeMap<String, HostAddress> {
+    let mut hosts: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .for_each(|line| {
+            let mut parts = line.split_whitespace();
+            let ip = match parts.next().map(|ip| ip.parse::<IpAddr>()) {
+                Some(Ok(ip)) => ip.to_string(),
+                _ => return,
+            };
+            parts.filter(|name| name.contains('.')).for_each(|name| {
+                let addresses = hosts.entry(format!("full:{}", name)).or_default();
+                if !addresses.contains(&ip) {
+                    addresses.push(ip.clone());
+                }
+            });
+        });
+    hosts
+        .into_iter()
+        .map(|(name, mut addresses)| {
+            let address = if addresses.len() == 1 {
+                HostAddress::Single(addresses.remove(0))
+            } else {
+                HostAddress::Multiple(addresses)
+            };
+            (name, address)
+        })
+        .collect()
+}
@@ -0,0 +1,348 @@
+use super::Inbound;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    net::{IpAddr, TcpListener},
+};
+use sysinfo::{Pid, System};
+use url::Url;
+
+/// Inbound protocols which can be managed by Venus
+pub const INBOUND_PROTOCOLS: [&str; 3] = ["socks", "http", "dokodemo-door"];
+
+/// Inbounds used by Venus itself, socks for speed test and api for stats
+pub const PROTECTED_INBOUNDS: [&str; 2] = ["socks", "api"];
+
+/// Process which holds a port
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PortOwner {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// Whether inbound port can be listened
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PortStatus {
+    pub tag: String,
+    pub port: u16,
+    pub free: bool,
+    /// The process holds the port, detected on Linux, macOS and Windows
+    pub owner: Option<PortOwner>,
+}
+
+/// Validate inbound before add it to core config
+///
+/// ## Arguments
+///
+/// `inbound`: the inbound to be added or updated
+/// `others`: other inbounds in core config
+///
+/// ## Return
+///
+/// All problems found in inbound
+pub fn validate_inbound(inbound: &Inbound, others: &[Inbound]) -> Vec<String> {
+    let mut errors = vec![];
+    if !INBOUND_PROTOCOLS.contains(&inbound.protocol.as_str()) {
+        errors.push(format!(
+            "unsupported inbound protocol {}, expect one of {}",
+            inbound.protocol,
+            INBOUND_PROTOCOLS.join(", ")
+        ));
+    }
+    if inbound.tag.is_empty() {
+        errors.push("inbound tag is empty".into());
+    } else if others.iter().any(|i| i.tag == inbound.tag) {
+        errors.push(format!("inbound tag {} already exists", inbound.tag));
+    }
+    if !inbound.listen.is_empty() && inbound.listen.parse::<IpAddr>().is_err() {
+        errors.push(format!("invalid listen address {}", inbound.listen));
+    }
+    match inbound.port.number() {
+        Some(0) | None => errors.push(format!("invalid inbound port {}", inbound.port)),
+        Some(port) => {
+            if let Some(other) = others.iter().find(|i| i.port.number() == Some(port)) {
+                errors.push(format!("port {} is used by inbound {}", port, other.tag));
+            }
+        }
+    }
+    errors.extend(validate_accounts(inbound));
+    if inbound.protocol == "dokodemo-door"
+        && !inbound.settings.extra.contains_key("address")
+        && inbound.settings.extra.get("followRedirect") != Some(&true.into())
+    {
+        errors.push(format!(
+            "dokodemo-door inbound {} needs address or followRedirect",
+            inbound.tag
+        ));
+    }
+    errors
+}
+
+fn validate_accounts(inbound: &Inbound) -> Vec<String> {
+    let settings = &inbound.settings;
+    let mut errors = vec![];
+    let mut users = HashSet::new();
+    for account in &settings.accounts {
+        if account.user.is_empty() || account.pass.is_empty() {
+            errors.push(format!(
+                "user and password of inbound {} can not be empty",
+                inbound.tag
+            ));
+        } else if !users.insert(&account.user) {
+            errors.push(format!(
+                "user {} of inbound {} is duplicated",
+                account.user, inbound.tag
+            ));
+        }
+    }
+    match settings.auth.as_deref() {
+        Some("password") if settings.accounts.is_empty() => errors.push(format!(
+            "inbound {} uses password auth but has no account",
+            inbound.tag
+        )),
+        Some("password") | Some("noauth") | None => {}
+        Some(auth) => errors.push(format!("invalid auth {} of inbound {}", auth, inbound.tag)),
+    }
+    errors
+}
+
+/// Generate auth settings by protocol,
+/// socks needs `auth` to enable accounts while http only needs `accounts`.
+pub fn normalize_auth(inbound: &mut Inbound) {
+    let settings = &mut inbound.settings;
+    match inbound.protocol.as_str() {
+        "socks" => {
+            settings.auth = Some(if settings.accounts.is_empty() {
+                "noauth".into()
+            } else {
+                "password".into()
+            });
+        }
+        "http" => settings.auth = None,
+        _ => {
+            settings.auth = None;
+            settings.accounts.clear();
+        }
+    }
+}
+
+/// Proxy url of inbound to connect through the core from this machine,
+/// with credentials of first account if the inbound requires auth
+///
+/// ## Arguments
+///
+/// `inbound`: socks or http inbound
+pub fn proxy_url(inbound: &Inbound) -> Result<String> {
+    let host = match listen_address(inbound) {
+        "0.0.0.0" | "::" => "127.0.0.1",
+        listen => listen,
+    };
+    proxy_url_with_host(inbound, host)
+}
+
+/// Proxy url of inbound with specified host, used by other devices
+///
+/// ## Arguments
+///
+/// `inbound`: socks or http inbound
+/// `host`: ip address of this machine
+pub fn proxy_url_with_host(inbound: &Inbound, host: &str) -> Result<String> {
+    let scheme = match inbound.protocol.as_str() {
+        "socks" => "socks5",
+        "http" => "http",
+        protocol => return Err(anyhow!("inbound protocol {} is not a proxy", protocol)),
+    };
+    let host = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+        _ => host.to_owned(),
+    };
+    let mut url = Url::parse(&format!("{}://{}:{}", scheme, host, inbound.port))?;
+    let auth_required = match scheme {
+        "socks5" => inbound.settings.auth.as_deref() == Some("password"),
+        _ => true,
+    };
+    if let Some(account) = inbound.settings.accounts.first().filter(|_| auth_required) {
+        url.set_username(&account.user)
+            .map_err(|_| anyhow!("cannot set proxy user"))?;
+        url.set_password(Some(&account.pass))
+            .map_err(|_| anyhow!("cannot set proxy password"))?;
+    }
+    Ok(url.to_string())
+}
+
+/// Listen address of inbound, core listens on all interfaces if it's empty
+pub fn listen_address(inbound: &Inbound) -> &str {
+    if inbound.listen.is_empty() {
+        "0.0.0.0"
+    } else {
+        &inbound.listen
+    }
+}
+
+/// Try to listen on port
+pub fn port_available(listen: &str, port: u16) -> bool {
+    TcpListener::bind((listen, port)).is_ok()
+}
+
+/// Find next free port after `port`
+///
+/// ## Arguments
+///
+/// `listen`: listen address
+/// `port`: start port, not included
+/// `taken`: ports used by other inbounds
+pub fn next_free_port(listen: &str, port: u16, taken: &[u16]) -> Option<u16> {
+    (port.saturating_add(1)..=u16::MAX).find(|p| !taken.contains(p) && port_available(listen, *p))
+}
+
+/// Find the process which is listening on TCP port,
+/// process name is read by sysinfo on all platforms
+pub fn port_owner(port: u16) -> Option<PortOwner> {
+    let pid = listening_pid(port)?;
+    let name = process_name(pid)?;
+    Some(PortOwner { pid, name })
+}
+
+/// Pid of the process listening on TCP port, read from procfs
+#[cfg(target_os = "linux")]
+fn listening_pid(port: u16) -> Option<u32> {
+    use std::fs;
+
+    // Socket inodes of listening port, the state 0A is LISTEN
+    let inodes = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields = line.split_whitespace().collect::<Vec<_>>();
+                    let local_port = fields.get(1)?.rsplit(':').next()?;
+                    let local_port = u16::from_str_radix(local_port, 16).ok()?;
+                    if local_port != port || fields.get(3) != Some(&"0A") {
+                        return None;
+                    }
+                    Some(format!("socket:[{}]", fields.get(9)?))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if inodes.is_empty() {
+        return None;
+    }
+    fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .find(|pid| {
+            fs::read_dir(format!("/proc/{}/fd", pid))
+                .map(|fds| {
+                    fds.flatten().any(|fd| {
+                        fs::read_link(fd.path())
+                            .map(|link| inodes.iter().any(|i| link.to_string_lossy() == *i))
+                            .unwrap_or(false)
+                    })
+                })
+                .unwrap_or(false)
+        })
+}
+
+/// Pid of the process listening on TCP port, read from `lsof`
+#[cfg(target_os = "macos")]
+fn listening_pid(port: u16) -> Option<u32> {
+    use std::process::Command;
+
+    let filter = format!("-iTCP:{}", port);
+    let output = Command::new("lsof")
+        .args(["-nP", filter.as_str(), "-sTCP:LISTEN", "-t"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().parse().ok())
+}
+
+/// Pid of the process listening on TCP port, read from `netstat`
+#[cfg(target_os = "windows")]
+fn listening_pid(port: u16) -> Option<u32> {
+    use std::{os::windows::process::CommandExt, process::Command};
+    // Don't popup console window
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    let output = Command::new("netstat")
+        .args(["-ano", "-p", "TCP"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| {
+            // Proto  Local Address  Foreign Address  State  PID
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let local_port = fields.get(1)?.rsplit(':').next()?.parse::<u16>().ok()?;
+            // state name is localized, listening sockets have no foreign port
+            let listening = fields.get(2)?.ends_with(":0");
+            if local_port != port || !listening {
+                return None;
+            }
+            fields.get(4)?.parse().ok()
+        })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn listening_pid(_port: u16) -> Option<u32> {
+    None
+}
+
+/// Name of running process, None if process exited
+fn process_name(pid: u32) -> Option<String> {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_process(pid);
+    system.process(pid).map(|p| p.name().to_owned())
+}
+
+/// Check ports of inbounds
+///
+/// ## Arguments
+///
+/// `inbounds`: inbounds to be checked
+/// `running`: inbounds of the running core, they will be released when core restarts
+/// `core_pid`: the running core process
+pub fn check_ports(
+    inbounds: &[Inbound],
+    running: &[Inbound],
+    core_pid: Option<u32>,
+) -> Vec<PortStatus> {
+    inbounds
+        .iter()
+        .filter_map(|inbound| {
+            let port = inbound.port.number()?;
+            if port_available(listen_address(inbound), port) {
+                return Some(PortStatus {
+                    tag: inbound.tag.clone(),
+                    port,
+                    free: true,
+                    owner: None,
+                });
+            }
+            let owner = port_owner(port);
+            let held_by_core = match (&owner, core_pid) {
+                (Some(owner), Some(pid)) => owner.pid == pid,
+                // owner can't be detected, assume it's the running core
+                (None, Some(_)) => running.iter().any(|i| i.port.number() == Some(port)),
+                _ => false,
+            };
+            Some(PortStatus {
+                tag: inbound.tag.clone(),
+                port,
+                free: held_by_core,
+                owner,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,183 @@
+use super::{inbound::proxy_url_with_host, CoreConfig, Inbound, LanSaved, LanSetting};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Listen on all interfaces
+pub const LAN_ALL: &str = "0.0.0.0";
+
+/// Address of network interface
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LanAddress {
+    pub interface: String,
+    pub ip: String,
+}
+
+/// Proxy url for other devices
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareUrl {
+    pub tag: String,
+    pub url: String,
+}
+
+/// Non-loopback addresses of this machine
+pub fn lan_addresses() -> Result<Vec<LanAddress>> {
+    let addresses = if_addrs::get_if_addrs()?
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .filter(|interface| match interface.ip() {
+            // link-local address needs scope id, other devices can't use it directly
+            IpAddr::V6(ip) => (ip.segments()[0] & 0xffc0) != 0xfe80,
+            IpAddr::V4(ip) => !ip.is_link_local(),
+        })
+        .map(|interface| LanAddress {
+            ip: interface.ip().to_string(),
+            interface: interface.name,
+        })
+        .collect();
+    Ok(addresses)
+}
+
+/// Rebind inbounds to LAN address, previous settings are saved for restore
+///
+/// ## Arguments
+///
+/// `core`: core config
+/// `setting`: LAN sharing setting, `address` and `inbounds` are used
+pub fn enable_lan(core: &mut CoreConfig, setting: &mut LanSetting) -> Result<()> {
+    if setting.enabled {
+        disable_lan(core, setting);
+    }
+    if setting.address.is_empty() {
+        setting.address = LAN_ALL.into();
+    }
+    let address = setting
+        .address
+        .parse::<IpAddr>()
+        .map_err(|_| anyhow!("invalid listen address {}", setting.address))?;
+    if !address.is_unspecified() && !lan_addresses()?.iter().any(|a| a.ip == setting.address) {
+        bail!("address {} is not found in network interfaces", address);
+    }
+    if setting.inbounds.is_empty() {
+        bail!("no inbound to share");
+    }
+
+    // check all inbounds first, so nothing is changed if any of them is invalid
+    let mut targets = vec![];
+    for tag in &setting.inbounds {
+        let index = core
+            .inbounds
+            .iter()
+            .position(|i| &i.tag == tag)
+            .ok_or(anyhow!("inbound {} not found", tag))?;
+        check_shareable(&core.inbounds[index])?;
+        targets.push(index);
+    }
+    setting.saved = targets
+        .iter()
+        .map(|index| {
+            let inbound = &mut core.inbounds[*index];
+            let saved = LanSaved {
+                tag: inbound.tag.clone(),
+                listen: inbound.listen.clone(),
+                allow_transparent: inbound.settings.allow_transparent,
+            };
+            inbound.listen = setting.address.clone();
+            // other devices may use it as a gateway proxy
+            if inbound.protocol == "http" {
+                inbound.settings.allow_transparent = true;
+            }
+            saved
+        })
+        .collect();
+    setting.enabled = true;
+    Ok(())
+}
+
+/// Only socks and http inbounds with password can be shared
+fn check_shareable(inbound: &Inbound) -> Result<()> {
+    match inbound.protocol.as_str() {
+        "socks" | "http" => {}
+        protocol => bail!(
+            "inbound {} with protocol {} can not be shared",
+            inbound.tag,
+            protocol
+        ),
+    }
+    let no_auth =
+        inbound.settings.accounts.is_empty() || inbound.settings.auth.as_deref() == Some("noauth");
+    if no_auth {
+        bail!(
+            "inbound {} must enable authentication before sharing to LAN",
+            inbound.tag
+        );
+    }
+    Ok(())
+}
+
+/// Shared inbounds must exist and keep authentication while sharing is enabled
+///
+/// ## Arguments
+///
+/// `inbounds`: inbounds which will be written to core config
+/// `setting`: LAN sharing setting
+pub fn check_lan(inbounds: &[Inbound], setting: &LanSetting) -> Result<()> {
+    if !setting.enabled {
+        return Ok(());
+    }
+    for tag in &setting.inbounds {
+        let inbound = inbounds.iter().find(|i| &i.tag == tag).ok_or(anyhow!(
+            "inbound {} is shared to LAN, turn off LAN sharing before removing it",
+            tag
+        ))?;
+        check_shareable(inbound)?;
+    }
+    Ok(())
+}
+
+/// Restore inbounds to the settings before sharing
+pub fn disable_lan(core: &mut CoreConfig, setting: &mut LanSetting) {
+    setting.saved.drain(..).for_each(|saved| {
+        if let Some(inbound) = core.inbounds.iter_mut().find(|i| i.tag == saved.tag) {
+            inbound.listen = saved.listen;
+            inbound.settings.allow_transparent = saved.allow_transparent;
+        }
+    });
+    setting.enabled = false;
+}
+
+/// Proxy urls of shared inbounds for every LAN address
+pub fn share_urls(core: &CoreConfig, setting: &LanSetting) -> Result<Vec<ShareUrl>> {
+    if !setting.enabled {
+        return Ok(vec![]);
+    }
+    // "0.0.0.0" listens on all IPv4 addresses, "::" listens on all addresses
+    let hosts = match setting.address.parse::<IpAddr>() {
+        Ok(address) if address.is_unspecified() => lan_addresses()?
+            .into_iter()
+            .map(|a| a.ip)
+            .filter(|ip| {
+                ip.parse::<IpAddr>()
+                    .map(|ip| ip.is_ipv4() || address.is_ipv6())
+                    .unwrap_or(false)
+            })
+            .collect(),
+        _ => vec![setting.address.clone()],
+    };
+    let mut urls = vec![];
+    for inbound in core
+        .inbounds
+        .iter()
+        .filter(|i| setting.inbounds.contains(&i.tag))
+    {
+        for host in &hosts {
+            urls.push(ShareUrl {
+                tag: inbound.tag.clone(),
+                url: proxy_url_with_host(inbound, host)?,
+            });
+        }
+    }
+    Ok(urls)
+}
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use std::net::IpAddr;
+
+/// Validate domain matcher syntax of routing rules and DNS
+///
+/// Supported: plain keyword, `domain:`, `full:`, `keyword:`, `dotless:`,
+/// `regexp:`, `geosite:` and `ext:file.dat:tag`
+pub fn validate_domain(matcher: &str) -> Result<()> {
+    let matcher = matcher.trim();
+    if matcher.is_empty() {
+        bail!("domain matcher is empty");
+    }
+    match matcher.split_once(':') {
+        None => is_domain_chars(matcher),
+        Some(("domain", domain)) | Some(("full", domain)) => {
+            if domain.is_empty() {
+                bail!("domain of {} is empty", matcher);
+            }
+            is_domain_chars(domain)
+        }
+        Some(("keyword", keyword)) | Some(("dotless", keyword)) => {
+            if keyword.contains(char::is_whitespace) {
+                bail!("keyword of {} contains whitespace", matcher);
+            }
+            Ok(())
+        }
+        Some(("regexp", regexp)) => {
+            Regex::new(regexp).map_err(|e| anyhow!("invalid regexp {} {}", regexp, e))?;
+            Ok(())
+        }
+        Some(("geosite", name)) => is_geo_name(name, matcher),
+        Some(("ext", ext)) => is_ext(ext, matcher),
+        Some((prefix, _)) => Err(anyhow!(
+            "unknown domain matcher prefix {} in {}",
+            prefix,
+            matcher
+        )),
+    }
+}
+
+/// Validate IP matcher syntax of routing rules and DNS
+///
+/// Supported: IP, CIDR, `geoip:`, `geoip:!` and `ext:file.dat:tag`
+pub fn validate_ip(matcher: &str) -> Result<()> {
+    let matcher = matcher.trim();
+    if matcher.is_empty() {
+        bail!("ip matcher is empty");
+    }
+    if let Some(code) = matcher.strip_prefix("geoip:") {
+        return is_geo_name(code.trim_start_matches('!'), matcher);
+    }
+    if let Some(ext) = matcher.strip_prefix("ext:") {
+        return is_ext(ext, matcher);
+    }
+    let (ip, prefix) = match matcher.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix)),
+        None => (matcher, None),
+    };
+    let ip = ip
+        .parse::<IpAddr>()
+        .map_err(|_| anyhow!("invalid ip {}", matcher))?;
+    if let Some(prefix) = prefix {
+        let prefix = prefix
+            .parse::<u8>()
+            .map_err(|_| anyhow!("invalid cidr prefix {}", matcher))?;
+        let max = if ip.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            bail!("cidr prefix of {} is larger than {}", matcher, max);
+        }
+    }
+    Ok(())
+}
+
+fn is_domain_chars(domain: &str) -> Result<()> {
+    let valid = domain
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '.' || c == '_');
+    if !valid {
+        bail!("invalid domain {}", domain);
+    }
+    Ok(())
+}
+
+/// Geosite category or geoip country code, may have attributes like `google@ads`
+fn is_geo_name(name: &str, matcher: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '@' || c == '!');
+    if !valid {
+        bail!("invalid geo name {}", matcher);
+    }
+    Ok(())
+}
+
+/// External geo data file, `ext:file.dat:tag`
+fn is_ext(ext: &str, matcher: &str) -> Result<()> {
+    match ext.split_once(':') {
+        Some((file, tag)) if !file.is_empty() && !tag.is_empty() => Ok(()),
+        _ => Err(anyhow!(
+            "invalid external matcher {}, expect ext:file:tag",
+            matcher
+        )),
+    }
+}
@@ -0,0 +1,516 @@
+use super::{assign_node_ids, LanSaved, Node, ProxyChain, RConfig, RuleSet, Subscription};
+use crate::utils::consts::VERSION;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::{collections::HashMap, fs, path::Path};
+use toml::{Table, Value};
+
+/// Upgrade step of `config.toml`,
+/// applied when stored version is lower than `version`
+struct Migration {
+    version: &'static str,
+    description: &'static str,
+    upgrade: fn(&mut Table) -> Result<()>,
+}
+
+/// All upgrade steps, must be ordered by version
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: "0.1.0",
+    description: "move root nodes into subscriptions",
+    upgrade: move_root_nodes,
+}];
+
+/// Parse version like "0.1.3" to comparable tuple, pre-release part is ignored
+fn parse_version(version: &str) -> (u64, u64, u64) {
+    let mut parts = version
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|p| p.parse::<u64>().unwrap_or(0));
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
+
+/// Upgrade `config.toml` content to current version.
+/// The old file will be copied to `config.toml.{version}.bak` before upgrade.
+///
+/// ## Arguments
+///
+/// `content`: content of `config.toml`
+/// `path`: path of `config.toml`
+///
+/// ## Return
+///
+/// Upgraded config, and whether it's changed and need to be written back
+pub fn migrate(content: &str, path: &Path) -> Result<(RConfig, bool)> {
+    let mut table = toml::from_str::<Table>(content)?;
+    let stored = table
+        .get("version")
+        .and_then(|v| v.as_str())
+        .unwrap_or("0.0.0")
+        .to_owned();
+    let stored_version = parse_version(&stored);
+    let current_version = parse_version(VERSION);
+    let downgrade = stored_version > current_version;
+    if downgrade {
+        warn!(
+            "Config version {} is newer than app version {}",
+            stored, VERSION
+        );
+    }
+
+    let steps = MIGRATIONS
+        .iter()
+        .filter(|m| stored_version < parse_version(m.version))
+        .collect::<Vec<_>>();
+    let mut upgraded = fill_defaults(&mut table)?;
+    for step in steps {
+        info!("Upgrade config to {}: {}", step.version, step.description);
+        (step.upgrade)(&mut table)
+            .map_err(|e| anyhow!("upgrade config to {} failed {}", step.version, e))?;
+        upgraded = true;
+    }
+    // Steps may remove fields which has default value
+    fill_defaults(&mut table)?;
+
+    // Keep the newer version, so the newer app will not skip its migrations
+    let mut changed = false;
+    if !downgrade {
+        if stored != VERSION {
+            changed = true;
+        }
+        table.insert("version".into(), Value::String(VERSION.into()));
+    }
+    let mut rua = Value::Table(table).try_into::<RConfig>()?;
+    upgraded |= migrate_node_ids(&mut rua);
+
+    // Upgraded config is written back by caller, the old file is not changed yet
+    if upgraded {
+        let backup = path.with_file_name(format!(
+            "{}.{}.bak",
+            path.file_name()
+                .ok_or(anyhow!("config path file name is empty"))?
+                .to_string_lossy(),
+            stored
+        ));
+        fs::copy(path, &backup)?;
+        info!("Backup old config to {}", backup.display());
+    }
+    Ok((rua, changed || upgraded))
+}
+
+/// Node ids were derived from node name and position in subscription,
+/// they changed when provider renamed or reordered nodes.
+/// Assign ids by `assign_node_ids` and update references to old ids,
+/// it's checked on every load so it's not a versioned step.
+///
+/// ## Return
+///
+/// Whether any id is changed
+fn migrate_node_ids(rua: &mut RConfig) -> bool {
+    let mut changed = false;
+    let mut renamed = HashMap::new();
+    for sub in rua.subscriptions.iter_mut() {
+        let old_ids = sub
+            .nodes
+            .iter()
+            .map(|n| n.node_id.clone())
+            .collect::<Vec<_>>();
+        assign_node_ids(&sub.name, &mut sub.nodes);
+        for (old, node) in old_ids.into_iter().zip(&sub.nodes) {
+            if old == node.node_id {
+                continue;
+            }
+            changed = true;
+            if let (Some(old), Some(new)) = (old, node.node_id.clone()) {
+                renamed.insert(old, new);
+            }
+        }
+    }
+    if renamed.is_empty() {
+        return changed;
+    }
+    let rename = |id: &mut String| {
+        if let Some(new) = renamed.get(id) {
+            *id = new.clone();
+        }
+    };
+    rename(&mut rua.current_id);
+    for chain in rua.chains.iter_mut() {
+        chain.nodes.iter_mut().for_each(rename);
+    }
+    rua.balancer.nodes.iter_mut().for_each(rename);
+    let overrides = std::mem::take(&mut rua.overrides.nodes);
+    rua.overrides.nodes = overrides
+        .into_iter()
+        .map(|(id, value)| (renamed.get(&id).cloned().unwrap_or(id), value))
+        .collect();
+    info!("Ids of {} nodes are migrated", renamed.len());
+    true
+}
+
+/// Add missing fields from default config,
+/// so adding non-optional field will not break old config
+///
+/// ## Return
+///
+/// Whether any field was added
+fn fill_defaults(table: &mut Table) -> Result<bool> {
+    let defaults = Table::try_from(RConfig::default())?;
+    let items = item_defaults()?;
+    Ok(merge_missing(table, &defaults, "", &items))
+}
+
+/// Default item of arrays of tables, keyed by dotted path of the array
+fn item_defaults() -> Result<Vec<(&'static str, Table)>> {
+    let subscription = Subscription {
+        name: String::new(),
+        url: String::new(),
+        nodes: vec![],
+    };
+    Ok(vec![
+        ("subscriptions", Table::try_from(subscription)?),
+        ("subscriptions.nodes", Table::try_from(Node::default())?),
+        ("ruleSets", Table::try_from(RuleSet::default())?),
+        ("chains", Table::try_from(ProxyChain::default())?),
+        ("lan.saved", Table::try_from(LanSaved::default())?),
+    ])
+}
+
+/// Add missing fields of `defaults` to `target`, recurse into tables and arrays of tables
+///
+/// ## Arguments
+///
+/// `path`: dotted path of `target`, empty for root
+/// `items`: default item of arrays of tables
+fn merge_missing(
+    target: &mut Table,
+    defaults: &Table,
+    path: &str,
+    items: &[(&str, Table)],
+) -> bool {
+    let mut changed = false;
+    for (key, value) in defaults {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match (target.get_mut(key), value) {
+            (None, value) => {
+                target.insert(key.clone(), value.clone());
+                changed = true;
+            }
+            (Some(Value::Table(target)), Value::Table(defaults)) => {
+                changed |= merge_missing(target, defaults, &path, items);
+            }
+            (Some(Value::Array(array)), _) => {
+                let item = items.iter().find(|(p, _)| *p == path);
+                if let Some((_, defaults)) = item {
+                    for target in array.iter_mut().filter_map(|v| v.as_table_mut()) {
+                        changed |= merge_missing(target, defaults, &path, items);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    changed
+}
+
+/// Nodes was stored in root `nodes` with their subscription name in `subs`
+fn move_root_nodes(table: &mut Table) -> Result<()> {
+    let nodes = match table.remove("nodes") {
+        Some(Value::Array(nodes)) => nodes,
+        _ => return Ok(()),
+    };
+    let subscriptions = table
+        .entry("subscriptions")
+        .or_insert_with(|| Value::Array(vec![]))
+        .as_array_mut()
+        .ok_or(anyhow!("subscriptions is not an array"))?;
+    for node in nodes {
+        let subs = node
+            .get("subs")
+            .and_then(|s| s.as_str())
+            .unwrap_or_default()
+            .to_owned();
+        let sub = subscriptions
+            .iter_mut()
+            .find(|s| s.get("name").and_then(|n| n.as_str()) == Some(&subs));
+        let sub = match sub {
+            Some(sub) => sub,
+            None => {
+                let mut sub = Table::new();
+                sub.insert("name".into(), Value::String(subs));
+                sub.insert("url".into(), Value::String(String::new()));
+                subscriptions.push(Value::Table(sub));
+                subscriptions
+                    .last_mut()
+                    .ok_or(anyhow!("subscriptions is empty"))?
+            }
+        };
+        sub.as_table_mut()
+            .ok_or(anyhow!("subscription is not a table"))?
+            .entry("nodes")
+            .or_insert_with(|| Value::Array(vec![]))
+            .as_array_mut()
+            .ok_or(anyhow!("subscription nodes is not an array"))?
+            .push(node);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::SubsAutoUpdate, utils::consts::SPEED_URL};
+    use std::{env, path::PathBuf};
+
+    /// Config files written by past releases
+    const FIXTURES: &[(&str, &str)] = &[
+        (
+            "0.0.9",
+            include_str!("../../tests/fixtures/config/0.0.9.toml"),
+        ),
+        (
+            "0.1.0",
+            include_str!("../../tests/fixtures/config/0.1.0.toml"),
+        ),
+        (
+            "0.1.1",
+            include_str!("../../tests/fixtures/config/0.1.1.toml"),
+        ),
+        (
+            "0.1.2",
+            include_str!("../../tests/fixtures/config/0.1.2.toml"),
+        ),
+        (
+            "0.1.3",
+            include_str!("../../tests/fixtures/config/0.1.3.toml"),
+        ),
+    ];
+
+    /// Write content to `config.toml` in a empty folder of system temp folder
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("venus-migrate-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    /// Migrate fixture of version
+    fn migrate_fixture(version: &str) -> RConfig {
+        let (_, content) = FIXTURES.iter().find(|(v, _)| *v == version).unwrap();
+        let path = write_config(version, content);
+        let (rua, _) = migrate(content, &path).unwrap();
+        rua
+    }
+
+    fn subscription<'a>(rua: &'a RConfig, name: &str) -> &'a Subscription {
+        rua.subscriptions.iter().find(|s| s.name == name).unwrap()
+    }
+
+    #[test]
+    fn migrate_past_releases() {
+        for (version, content) in FIXTURES {
+            let path = write_config(version, content);
+            let (rua, changed) = migrate(content, &path)
+                .unwrap_or_else(|e| panic!("migrate {} failed {}", version, e));
+            assert!(changed, "{}", version);
+            assert_eq!(rua.version, VERSION);
+            assert!(path
+                .with_file_name(format!("config.toml.{}.bak", version))
+                .exists());
+
+            // migrated config can be read again without changes
+            let content = toml::to_string(&rua).unwrap();
+            let (_, changed) = migrate(&content, &path).unwrap();
+            assert!(!changed, "{}", version);
+        }
+    }
+
+    #[test]
+    fn move_root_nodes_to_subscriptions() {
+        let rua = migrate_fixture("0.0.9");
+        // selected node was not saved before 0.1.0
+        assert_eq!(rua.current_id, "");
+        let default = subscription(&rua, "default");
+        assert_eq!(
+            default.url,
+            "https://sub.example.net/api/v1/client/subscribe?token=2f9c8e"
+        );
+        let names = default
+            .nodes
+            .iter()
+            .map(|n| n.ps.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["JP Tokyo 01", "HK 02 | x1.5"]);
+        // nodes added by manually have no subscription
+        let manual = subscription(&rua, "manual");
+        assert_eq!(manual.url, "");
+        assert_eq!(manual.nodes.len(), 1);
+        assert_eq!(manual.nodes[0].add, "203.0.113.7");
+        assert_eq!(manual.nodes[0].raw_link, None);
+    }
+
+    #[test]
+    fn fill_settings_of_0_1_0() {
+        let rua = migrate_fixture("0.1.0");
+        // settings was added in 0.1.2
+        assert_eq!(rua.settings.speed_url, SPEED_URL);
+        assert!(matches!(
+            rua.settings.update_subs,
+            Some(SubsAutoUpdate::Off)
+        ));
+        // node fields added later are filled, test results are kept
+        let node = &subscription(&rua, "default").nodes[0];
+        assert_eq!(node.node_id.as_deref(), Some(rua.current_id.as_str()));
+        assert_eq!(node.scy, "");
+        assert_eq!(node.fp, "");
+        assert_eq!(node.delay, Some(86));
+        assert_eq!(node.connectivity, Some(true));
+    }
+
+    #[test]
+    fn keep_subscriptions_of_0_1_1() {
+        let rua = migrate_fixture("0.1.1");
+        let names = rua
+            .subscriptions
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["default", "backup"]);
+        let node = &subscription(&rua, "backup").nodes[0];
+        assert_eq!(node.node_id.as_deref(), Some(rua.current_id.as_str()));
+        assert_eq!(node.net, "grpc");
+        assert_eq!(node.speed, Some(4.27));
+        assert_eq!(
+            subscription(&rua, "default").nodes[1].connectivity,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn keep_speed_url_of_0_1_2() {
+        let rua = migrate_fixture("0.1.2");
+        assert_eq!(
+            rua.settings.speed_url,
+            "https://speed.cloudflare.com/__down?bytes=25000000"
+        );
+        // auto update was added in 0.1.3
+        assert!(matches!(
+            rua.settings.update_subs,
+            Some(SubsAutoUpdate::Off)
+        ));
+        assert_eq!(rua.settings.update_time, None);
+    }
+
+    #[test]
+    fn keep_auto_update_of_0_1_3() {
+        let rua = migrate_fixture("0.1.3");
+        assert!(matches!(
+            rua.settings.update_subs,
+            Some(SubsAutoUpdate::Time)
+        ));
+        assert_eq!(rua.settings.update_time, Some(12));
+        // settings added after 0.1.3 use defaults
+        let defaults = RConfig::default();
+        assert_eq!(rua.mode, defaults.mode);
+        assert!(rua.rule_sets.is_empty());
+        assert!(rua.chains.is_empty());
+        assert!(rua.overrides.nodes.is_empty());
+    }
+
+    #[test]
+    fn migrate_node_id_references() {
+        let (_, content) = FIXTURES[4];
+        let mut table = toml::from_str::<Table>(content).unwrap();
+        let old_id = "d11504146ae779b40d3755704837b543";
+        let extra = toml::from_str::<Table>(&format!(
+            r#"
+            [[chains]]
+            name = "relay"
+            nodes = ["b4f26feb1a414f6abe128a5eb390b2f4", "{0}"]
+
+            [balancer]
+            nodes = ["{0}", "removed"]
+
+            [overrides.nodes.{0}]
+            mux = true
+
+            [overrides.nodes.removed]
+            mux = false
+            "#,
+            old_id
+        ))
+        .unwrap();
+        table.extend(extra);
+        let content = toml::to_string(&table).unwrap();
+        let path = write_config("node-ids", &content);
+        let (rua, changed) = migrate(&content, &path).unwrap();
+        assert!(changed);
+
+        let new_id = subscription(&rua, "backup").nodes[0]
+            .node_id
+            .clone()
+            .unwrap();
+        assert_ne!(new_id, old_id);
+        assert_eq!(rua.current_id, new_id);
+        assert_eq!(rua.chains[0].nodes[1], new_id);
+        assert_eq!(rua.balancer.nodes, [new_id.as_str(), "removed"]);
+        // overrides of removed nodes are kept
+        let ids = rua.overrides.nodes.keys().collect::<Vec<_>>();
+        assert_eq!(ids, [&new_id, "removed"]);
+    }
+
+    #[test]
+    fn keep_node_id_when_renamed() {
+        let rua = migrate_fixture("0.1.3");
+        let mut nodes = subscription(&rua, "default").nodes.clone();
+        let ids = nodes.iter().map(|n| n.node_id.clone()).collect::<Vec<_>>();
+        nodes.reverse();
+        nodes[0].ps = "HK 02 | x2".into();
+        assign_node_ids("default", &mut nodes);
+        nodes.reverse();
+        assert!(nodes
+            .iter()
+            .map(|n| n.node_id.clone())
+            .eq(ids.iter().cloned()));
+
+        // same node in one subscription
+        let mut nodes = vec![nodes[0].clone(), nodes[0].clone()];
+        assign_node_ids("default", &mut nodes);
+        assert_eq!(nodes[0].node_id, ids[0]);
+        assert_ne!(nodes[1].node_id, ids[0]);
+    }
+
+    #[test]
+    fn fill_defaults_of_array_items() {
+        let mut table = toml::from_str::<Table>(
+            r#"
+            [[subscriptions]]
+            name = "default"
+            "#,
+        )
+        .unwrap();
+        assert!(fill_defaults(&mut table).unwrap());
+        let sub = &table["subscriptions"][0];
+        assert_eq!(sub.get("url").and_then(|u| u.as_str()), Some(""));
+        assert!(sub.get("nodes").map(|n| n.is_array()).unwrap_or(false));
+    }
+
+    #[test]
+    fn keep_newer_version() {
+        let content = FIXTURES[4].1.replace("0.1.3", "99.0.0");
+        let path = write_config("newer", &content);
+        let (rua, _) = migrate(&content, &path).unwrap();
+        assert_eq!(rua.version, "99.0.0");
+    }
+}
@@ -1,18 +1,53 @@
-pub use self::thing::*;
+use self::{
+    balancer::apply_balancer,
+    bind::apply_bind,
+    chain::{find_measured, outbounds_by_id, replace_outbounds},
+    migrate::migrate,
+    paths::AppPaths,
+    profile::{load_profile, profile_exists, save_profile, Profile},
+    routing::mode_rules,
+    rule_set::rule_set_rules,
+    snapshot::take_snapshot,
+    watcher::{check_writable, mark_known},
+};
+pub use self::{overlay::*, thing::*};
 use crate::{
-    utils::consts::{NAME, VERSION},
+    logger::apply_log_setting,
+    message::{ConfigMsg, MSG_TX},
+    utils::file::atomic_write,
     CONFIG, LOGGING,
 };
 use anyhow::{anyhow, Result};
-use log::error;
+use log::{error, info, warn};
+use serde_json::{Map, Value};
 use std::{
-    fs::{self, File, OpenOptions},
-    io::{Read, Write},
+    collections::HashMap,
+    fs::{self, File},
+    io::Read,
     path::{Path, PathBuf},
     sync::atomic::Ordering,
 };
+use tauri::async_runtime;
 
+pub mod balancer;
+pub mod bind;
+pub mod bundle;
+pub mod chain;
+pub mod dns;
+pub mod inbound;
+pub mod lan;
+pub mod matcher;
+pub mod migrate;
+pub mod node_override;
+pub mod overlay;
+pub mod paths;
+pub mod profile;
+pub mod routing;
+pub mod rule_set;
+pub mod simulate;
+pub mod snapshot;
 pub mod thing;
+pub mod watcher;
 
 impl Default for VConfig {
     fn default() -> Self {
@@ -30,10 +65,13 @@ impl VConfig {
         let r_config = RConfig::default();
 
         Self {
+            paths: AppPaths::default(),
             core: None,
             rua: r_config,
             rua_path: PathBuf::new(),
             core_path: PathBuf::new(),
+            overlay_path: PathBuf::new(),
+            runtime_path: PathBuf::new(),
         }
     }
 
@@ -41,28 +79,28 @@ impl VConfig {
     ///
     /// ## Arguments
     ///
-    /// `resource_path`: the store path of `config.json` and `config.toml`
+    /// `resource_path`: the folder of bundled default `config.json`
     pub fn init(&mut self, resource_path: &Path) -> Result<()> {
         let mut core_default = PathBuf::from(resource_path);
         core_default.push("config.json");
 
-        let home = home::home_dir()
-            .map(|path| {
-                let mut path = path;
-                path.push(format!(".config/{}", NAME));
-                path
-            })
-            .unwrap_or_else(|| {
-                error!("Cannot detect user home folder, use /usr/local instead");
-                PathBuf::from(format!("/usr/local/{}", NAME))
-            });
-        let mut core_path = PathBuf::from(&home);
-        core_path.push("config.json");
-        let mut rua_path = PathBuf::from(&home);
-        rua_path.push("config.toml");
+        let paths = AppPaths::resolve()?;
+        paths.migrate_legacy();
+        info!(
+            "Config folder {}, data folder {}, log folder {}",
+            paths.config.display(),
+            paths.data.display(),
+            paths.state.display()
+        );
+        let home = paths.config.clone();
+        let core_path = home.join("config.json");
+        let rua_path = home.join("config.toml");
 
+        self.paths = paths;
         self.core_path = core_path.clone();
         self.rua_path = rua_path.clone();
+        self.overlay_path = home.join("overlay.json");
+        self.runtime_path = home.join("runtime.json");
 
         detect_and_create(&core_path, core_default)?;
         if !rua_path.exists() {
@@ -70,13 +108,62 @@ impl VConfig {
         }
 
         self.reload()?;
+        // a broken overlay should not stop core from starting
+        let generated = self.generate_core()?;
+        let mut runtime = generated.clone();
+        let overlay =
+            Overlay::load(&self.overlay_path).and_then(|overlay| overlay.apply(&mut runtime));
+        let runtime = match overlay {
+            Ok(_) => runtime,
+            Err(err) => {
+                let err = format!("Apply overlay failed, runtime config without overlay {err}");
+                error!("{err}");
+                async_runtime::spawn(async move {
+                    let _ = MSG_TX.lock().await.send(ConfigMsg::ConfigError(err)).await;
+                });
+                generated
+            }
+        };
+        self.write_runtime_value(&runtime)?;
 
         if self.rua.logging {
             LOGGING.store(true, Ordering::Relaxed);
         }
+        let _ = apply_log_setting(&self.log_dir(), &self.rua.settings.log)
+            .map_err(|e| error!("apply log setting failed {e}"));
         Ok(())
     }
 
+    /// The folder of config files
+    fn config_dir(&self) -> PathBuf {
+        self.paths.config.clone()
+    }
+
+    /// The folder of log files, in the state folder
+    pub fn log_dir(&self) -> PathBuf {
+        self.paths.state.join("logs")
+    }
+
+    /// The writable folder of geo data files, used as `V2RAY_LOCATION_ASSET`
+    pub fn asset_dir(&self) -> PathBuf {
+        self.paths.data.join("assets")
+    }
+
+    /// The folder of downloaded rule set entries
+    pub fn rule_set_dir(&self) -> PathBuf {
+        self.paths.data.join("rules")
+    }
+
+    /// The folder of config files snapshots
+    pub fn snapshot_dir(&self) -> PathBuf {
+        self.config_dir().join("snapshots")
+    }
+
+    /// The folder of named profiles
+    pub fn profile_dir(&self) -> PathBuf {
+        self.config_dir().join("profiles")
+    }
+
     /// Reload core and rua config from file
     pub fn reload(&mut self) -> Result<()> {
         self.reload_core()?;
@@ -84,43 +171,162 @@ impl VConfig {
         Ok(())
     }
 
+    /// Reload rua config file, upgrade it if it's from old version
     pub fn reload_rua(&mut self) -> Result<()> {
         let mut config_file = File::open(&self.rua_path)?;
         let mut buffer = String::new();
         config_file.read_to_string(&mut buffer)?;
-        let mut rua_config = toml::from_str::<RConfig>(&buffer)?;
-        // TODO upgrade from old version
-        rua_config.version = VERSION.into();
+        let (rua_config, changed) = migrate(&buffer, &self.rua_path)?;
+        mark_known(&self.rua_path, buffer.as_bytes());
         self.rua = rua_config;
+        if changed {
+            self.write_rua()?;
+        }
         Ok(())
     }
 
     /// Reload core config file from VConfig
     pub fn reload_core(&mut self) -> Result<()> {
-        let core_file = File::open(&self.core_path)?;
-        let core_config: CoreConfig = serde_json::from_reader(core_file)?;
+        let content = fs::read(&self.core_path)?;
+        let core_config: CoreConfig = serde_json::from_slice(&content)?;
+        mark_known(&self.core_path, &content);
         self.core = Some(core_config);
         Ok(())
     }
 
     ///  Write core config to config file
     pub fn write_core(&mut self) -> Result<()> {
+        check_writable(&self.core_path)?;
         let config = self.core.as_ref().ok_or(anyhow!("core config is empty"))?;
-        let core_file = OpenOptions::new().write(true).open(&self.core_path)?;
-        core_file.set_len(0)?;
-        serde_json::to_writer_pretty(&core_file, &config)?;
+        let content = serde_json::to_string_pretty(&config)?;
+        let _ = take_snapshot(&self.snapshot_dir(), &self.core_path, content.as_bytes())
+            .map_err(|e| error!("take snapshot of core config failed {e}"));
+        atomic_write(&self.core_path, content.as_bytes())?;
+        mark_known(&self.core_path, content.as_bytes());
+        self.write_runtime()?;
+        Ok(())
+    }
+
+    /// Generate the config which core actually runs with,
+    /// rules of routing mode, balancer group, outbound bind and user overlay are applied.
+    pub fn runtime_core(&self) -> Result<Value> {
+        self.runtime_core_with(&Overlay::load(&self.overlay_path)?)
+    }
+
+    /// Generate runtime config with the overlay instead of the overlay file,
+    /// used to preview and check an overlay before it's saved
+    pub fn runtime_core_with(&self, overlay: &Overlay) -> Result<Value> {
+        let mut runtime = self.generate_core()?;
+        overlay.apply(&mut runtime)?;
+        Ok(runtime)
+    }
+
+    /// Generate core config without user overlay,
+    /// rules of routing mode, balancer group and outbound bind are applied.
+    fn generate_core(&self) -> Result<Value> {
+        let mut config = self.core.clone().ok_or(anyhow!("core config is empty"))?;
+        config.routing.rules.retain(|rule| !rule.disabled);
+        // rule sets are matched after system and hand-written rules
+        let mut rules = rule_set_rules(&self.rule_set_dir(), &self.rua.rule_sets);
+        config.routing.rules.append(&mut rules);
+        config.routing.rules = mode_rules(self.rua.mode, &config.routing.rules);
+        apply_balancer(&mut config, &self.rua);
+        apply_bind(&mut config, &self.rua.bind);
+        Ok(serde_json::to_value(config)?)
+    }
+
+    /// Write runtime config to file
+    pub fn write_runtime(&self) -> Result<()> {
+        self.write_runtime_value(&self.runtime_core()?)
+    }
+
+    fn write_runtime_value(&self, runtime: &Value) -> Result<()> {
+        let content = serde_json::to_string_pretty(runtime)?;
+        atomic_write(&self.runtime_path, content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Write user overlay to file
+    pub fn write_overlay(&self, overlay: &Overlay) -> Result<()> {
+        let content = serde_json::to_string_pretty(overlay)?;
+        atomic_write(&self.overlay_path, content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Current routing, DNS, overlay and selected node as a profile
+    pub fn capture_profile(&self, name: &str) -> Result<Profile> {
+        let core = self.core.as_ref().ok_or(anyhow!("core config is empty"))?;
+        Ok(Profile {
+            name: name.to_owned(),
+            routing: core.routing.clone(),
+            dns: core.dns.clone(),
+            overlay: Overlay::load(&self.overlay_path)?,
+            node_id: self.rua.current_id.clone(),
+        })
+    }
+
+    /// Activate profile and regenerate config files, core need to be restarted.
+    /// Current settings will be saved to the active profile before switching,
+    /// so changes made after activation will not be lost.
+    ///
+    /// ## Arguments
+    ///
+    /// `name`: profile name
+    pub fn activate_profile(&mut self, name: &str) -> Result<()> {
+        let dir = self.profile_dir();
+        let profile = load_profile(&dir, name)?;
+        let active = self.rua.profile.clone();
+        if !active.is_empty() && active != name && profile_exists(&dir, &active)? {
+            save_profile(&dir, &self.capture_profile(&active)?)?;
+        }
+
+        let outbounds = if profile.node_id.is_empty() {
+            None
+        } else {
+            outbounds_by_id(&profile.node_id, &self.rua, "proxy")
+                .map_err(|e| warn!("Node of profile {} not found, keep current node {e}", name))
+                .ok()
+        };
+        let previous_core = self.core.clone();
+        let previous_id = self.rua.current_id.clone();
+        let core = self.core.as_mut().ok_or(anyhow!("core config is empty"))?;
+        core.routing = profile.routing.clone();
+        core.dns = profile.dns.clone();
+        if let Some(outbounds) = outbounds {
+            replace_outbounds(core, "proxy", outbounds);
+            self.rua.current_id = profile.node_id.clone();
+        }
+        // runtime is generated from overlay file when writing core,
+        // restore the previous overlay and core if core can't be written
+        let previous_overlay = fs::read(&self.overlay_path).ok();
+        let written = self
+            .write_overlay(&profile.overlay)
+            .and_then(|_| self.write_core());
+        if let Err(e) = written {
+            self.core = previous_core;
+            self.rua.current_id = previous_id;
+            let restored = match previous_overlay {
+                Some(content) => atomic_write(&self.overlay_path, &content),
+                None => fs::remove_file(&self.overlay_path).map_err(Into::into),
+            };
+            if let Err(err) = restored {
+                error!("Restore overlay failed {}", err);
+            }
+            return Err(e);
+        }
+        self.rua.profile = name.to_owned();
+        self.write_rua()?;
+        info!("Profile {} activated", name);
         Ok(())
     }
 
     pub fn write_rua(&mut self) -> Result<()> {
-        let mut rua_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.rua_path)?;
+        check_writable(&self.rua_path)?;
         let rua_string = toml::to_string(&self.rua)?;
-        rua_file.set_len(0)?;
-        rua_file.write_all(rua_string.as_bytes())?;
+        let _ = take_snapshot(&self.snapshot_dir(), &self.rua_path, rua_string.as_bytes())
+            .map_err(|e| error!("take snapshot of rua config failed {e}"));
+        atomic_write(&self.rua_path, rua_string.as_bytes())?;
+        mark_known(&self.rua_path, rua_string.as_bytes());
         Ok(())
     }
 }
@@ -144,6 +350,28 @@ fn detect_and_create(target_path: &PathBuf, default_path: PathBuf) -> Result<()>
     Ok(())
 }
 
+/// Give subscription nodes ids derived from subscription name, address, port and user id,
+/// so ids are kept when provider renames or reorders nodes.
+/// Same nodes in one subscription are numbered in order.
+///
+/// ## Arguments
+///
+/// `subscription`: subscription name
+/// `nodes`: nodes of the subscription
+pub fn assign_node_ids(subscription: &str, nodes: &mut [Node]) {
+    let mut seen = HashMap::new();
+    for node in nodes {
+        let key = format!("{}-{}-{}-{}", subscription, node.add, node.port, node.id);
+        let count = seen.entry(key.clone()).or_insert(0);
+        let id = match *count {
+            0 => md5::compute(&key),
+            n => md5::compute(format!("{}-{}", key, n)),
+        };
+        *count += 1;
+        node.node_id = Some(format!("{:?}", id));
+    }
+}
+
 /// Find target node by node id.
 ///
 /// ## Argments
@@ -154,16 +382,12 @@ fn detect_and_create(target_path: &PathBuf, default_path: PathBuf) -> Result<()>
 /// ## Return
 ///
 /// Option, if target node is found return a reference, or None.
-pub fn find_node<'a>(node_id: &String, rua: &'a RConfig) -> Result<&'a Node> {
-    let mut node = None;
-    rua.subscriptions.iter().for_each(|sub| {
-        node = sub
-            .nodes
-            .iter()
-            .find(|n| n.node_id.as_ref().unwrap_or(&"".to_string()) == node_id);
-    });
-    let node = node.ok_or(anyhow!("node {} not found", node_id))?;
-    Ok(node)
+pub fn find_node<'a>(node_id: &str, rua: &'a RConfig) -> Result<&'a Node> {
+    rua.subscriptions
+        .iter()
+        .flat_map(|sub| sub.nodes.iter())
+        .find(|n| n.node_id.as_deref() == Some(node_id))
+        .ok_or(anyhow!("node {} not found", node_id))
 }
 
 /// Build core outbound item.
@@ -174,80 +398,193 @@ pub fn find_node<'a>(node_id: &String, rua: &'a RConfig) -> Result<&'a Node> {
 /// `node`: node info from subscription
 /// `tag`: outbound tag name
 pub fn proxy_builder(node: &Node, tag: String) -> Result<Outbound> {
+    let port = node.port.trim();
     let vmess = Vmess {
         address: node.add.clone(),
-        port: node.port.parse()?,
+        port: port
+            .parse()
+            .map_err(|e| anyhow!("invalid port {} of node {} {}", port, node.ps, e))?,
         users: vec![CoreUser {
             id: node.id.clone(),
-            alter_id: node.aid.parse()?,
-            email: "rua@rua.rua".into(),
-            security: "auto".into(),
+            alter_id: match node.aid.trim() {
+                "" => 0,
+                aid => aid
+                    .parse()
+                    .map_err(|e| anyhow!("invalid alter id {} of node {} {}", aid, node.ps, e))?,
+            },
+            security: if node.scy.is_empty() {
+                "auto".into()
+            } else {
+                node.scy.clone()
+            },
+            ..Default::default()
         }],
+        ..Default::default()
     };
 
     let proxy = Outbound {
         tag,
         protocol: "vmess".into(),
-        settings: OutboundSettings { vnext: vec![vmess] },
+        settings: OutboundSettings {
+            vnext: Some(vec![vmess]),
+            ..Default::default()
+        },
         stream_settings: Some(stream_settings_builder(node)?),
-        proxy_setting: None,
-        mux: None,
+        ..Default::default()
     };
     Ok(proxy)
 }
 
-/// Build outbound stream setting with node in subscription
+/// Split comma separated list in share link, empty items are removed
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Build outbound stream setting with node in subscription.
+/// Fields follow v2rayN share link format, `type`, `host` and `path`
+/// mean different things in different networks.
 pub fn stream_settings_builder(node: &Node) -> Result<StreamSettings> {
-    let setting = StreamSettings {
-        network: node.net.clone(),
+    let header_type = if node.type_field.is_empty() {
+        "none".to_owned()
+    } else {
+        node.type_field.clone()
+    };
+    // links without net are tcp
+    let network = if node.net.is_empty() {
+        "tcp"
+    } else {
+        node.net.as_str()
+    };
+    let mut setting = StreamSettings {
+        network: network.to_owned(),
         security: if !node.tls.is_empty() {
             node.tls.clone()
         } else {
             "none".into()
         },
-        tls_settings: if !node.tls.is_empty() {
-            Some(TlsSettings {
-                alpn: vec![],
-                server_name: node.host.clone(),
-                certificates: vec![],
-                allow_insecure: false,
-                disable_system_root: false,
-            })
+        ..Default::default()
+    };
+
+    if !node.tls.is_empty() {
+        let server_name = if !node.sni.is_empty() {
+            node.sni.clone()
         } else {
-            None
-        },
-        tcp_settings: None,
-        kcp_settings: None,
-        ws_settings: if node.net.as_str() == "ws" {
-            Some(WsSettings {
+            // same as v2rayN, use first host when sni is empty
+            split_list(&node.host)
+                .into_iter()
+                .next()
+                .unwrap_or_default()
+        };
+        setting.tls_settings = Some(TlsSettings {
+            server_name,
+            allow_insecure: node.allow_insecure(),
+            alpn: split_list(&node.alpn),
+            fingerprint: node.fp.clone(),
+            ..Default::default()
+        });
+    }
+
+    match network {
+        "tcp" if header_type == "http" => {
+            let paths = split_list(&node.path);
+            let mut headers = Map::new();
+            let hosts = split_list(&node.host);
+            if !hosts.is_empty() {
+                headers.insert("Host".into(), hosts.into());
+            }
+            setting.tcp_settings = Some(TcpSettings {
+                header: TcpHeader {
+                    type_field: header_type,
+                    request: Some(Request {
+                        version: Some("1.1".into()),
+                        method: Some("GET".into()),
+                        path: if paths.is_empty() {
+                            vec!["/".into()]
+                        } else {
+                            paths
+                        },
+                        headers,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        }
+        "kcp" => {
+            setting.kcp_settings = Some(KcpSettings {
+                header: Some(KcpHeader {
+                    type_field: header_type,
+                    ..Default::default()
+                }),
+                // path is kcp seed
+                seed: if node.path.is_empty() {
+                    None
+                } else {
+                    Some(node.path.clone())
+                },
+                ..Default::default()
+            });
+        }
+        "ws" => {
+            setting.ws_settings = Some(WsSettings {
                 path: node.path.clone(),
                 headers: WsHeaders {
                     host: node.host.clone(),
+                    ..Default::default()
                 },
-            })
-        } else {
-            None
-        },
-        http_settings: None,
-        ds_settings: None,
-        quic_settings: None,
-        sockopt: None,
-    };
+                ..Default::default()
+            });
+        }
+        "h2" | "http" => {
+            setting.http_settings = Some(HttpSettings {
+                host: split_list(&node.host),
+                path: if node.path.is_empty() {
+                    "/".into()
+                } else {
+                    node.path.clone()
+                },
+                ..Default::default()
+            });
+        }
+        "quic" => {
+            // host is quic encryption method, path is its key
+            setting.quic_settings = Some(QuicSettings {
+                security: if node.host.is_empty() {
+                    "none".into()
+                } else {
+                    node.host.clone()
+                },
+                key: node.path.clone(),
+                header: KcpHeader {
+                    type_field: header_type,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        }
+        "grpc" => {
+            // path is service name, type is gun or multi mode
+            setting.grpc_settings = Some(GrpcSettings {
+                service_name: node.path.clone(),
+                multi_mode: header_type == "multi",
+                ..Default::default()
+            });
+        }
+        _ => {}
+    }
 
     Ok(setting)
 }
 
-/// Change node's connectivity field in config
+/// Change node's or chain's connectivity field in config
 pub async fn change_connectivity(id: &str, connectivity: bool) -> Result<()> {
     let mut config = CONFIG.lock().await;
-    let mut node = None;
-    config.rua.subscriptions.iter_mut().for_each(|sub| {
-        node = sub
-            .nodes
-            .iter_mut()
-            .find(|n| n.node_id.as_ref().unwrap_or(&"".into()) == id);
-    });
-    let node = node.ok_or(anyhow!("node {} not found", &id))?;
-    node.connectivity = Some(connectivity);
+    find_measured(id, &mut config.rua)?.set_connectivity(connectivity);
     Ok(())
 }
@@ -0,0 +1,122 @@
+use super::{merge_patch, proxy_builder, Mux, Node, Outbound, OutboundOverride, RConfig};
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+const DOMAIN_STRATEGIES: [&str; 4] = ["AsIs", "UseIP", "UseIPv4", "UseIPv6"];
+/// Mux concurrency used when mux is enabled without concurrency
+const DEFAULT_CONCURRENCY: u32 = 8;
+
+/// Validate override fields
+pub fn validate_override(value: &OutboundOverride) -> Result<()> {
+    if let Some(concurrency) = value.mux_concurrency {
+        if !(1..=1024).contains(&concurrency) {
+            bail!("mux concurrency {} out of range 1-1024", concurrency);
+        }
+    }
+    if let Some(strategy) = &value.domain_strategy {
+        if !DOMAIN_STRATEGIES.contains(&strategy.as_str()) {
+            bail!(
+                "invalid domain strategy {}, expected one of {}",
+                strategy,
+                DOMAIN_STRATEGIES.join(", ")
+            );
+        }
+    }
+    if let Some(interface) = &value.interface {
+        if interface.trim().is_empty() {
+            bail!("interface name is empty");
+        }
+    }
+    if let Some(patch) = &value.patch {
+        parse_patch(patch)?;
+    }
+    Ok(())
+}
+
+fn parse_patch(patch: &str) -> Result<Value> {
+    let value: Value =
+        serde_json::from_str(patch).map_err(|e| anyhow!("invalid outbound patch {}", e))?;
+    if !value.is_object() {
+        bail!("outbound patch must be a JSON object");
+    }
+    Ok(value)
+}
+
+/// Override of node, node fields take precedence over subscription default.
+/// Patches of both are kept and applied in order.
+fn node_overrides<'a>(node: &Node, rua: &'a RConfig) -> Vec<&'a OutboundOverride> {
+    let overrides = &rua.overrides;
+    let subscription = node
+        .subs
+        .as_ref()
+        .and_then(|name| overrides.subscriptions.get(name));
+    let node = node.node_id.as_ref().and_then(|id| overrides.nodes.get(id));
+    subscription.into_iter().chain(node).collect()
+}
+
+/// Apply overrides to outbound in order, later override wins
+///
+/// ## Arguments
+///
+/// `outbound`: outbound generated from node
+/// `overrides`: subscription default then node override
+pub fn apply_overrides(outbound: &mut Outbound, overrides: &[&OutboundOverride]) -> Result<()> {
+    for value in overrides {
+        if value.mux.is_some() || value.mux_concurrency.is_some() {
+            let mux = outbound.mux.get_or_insert_with(|| Mux {
+                enabled: true,
+                concurrency: DEFAULT_CONCURRENCY,
+                ..Default::default()
+            });
+            if let Some(enabled) = value.mux {
+                mux.enabled = enabled;
+            }
+            if let Some(concurrency) = value.mux_concurrency {
+                mux.concurrency = concurrency;
+            }
+        }
+        if let Some(strategy) = &value.domain_strategy {
+            outbound.domain_strategy = Some(strategy.clone());
+        }
+        if value.mark.is_some() || value.tcp_fast_open.is_some() || value.interface.is_some() {
+            let sockopt = outbound
+                .stream_settings
+                .get_or_insert_with(Default::default)
+                .sockopt
+                .get_or_insert_with(Default::default);
+            if value.mark.is_some() {
+                sockopt.mark = value.mark;
+            }
+            if value.tcp_fast_open.is_some() {
+                sockopt.tcp_fast_open = value.tcp_fast_open;
+            }
+            if value.interface.is_some() {
+                sockopt.bind_to_device = value.interface.clone();
+            }
+        }
+    }
+    for patch in overrides.iter().filter_map(|o| o.patch.as_ref()) {
+        let tag = outbound.tag.clone();
+        let mut target = serde_json::to_value(&*outbound)?;
+        merge_patch(&mut target, &parse_patch(patch)?);
+        *outbound = serde_json::from_value(target)
+            .map_err(|e| anyhow!("outbound patch produces invalid outbound {}", e))?;
+        // tag is referenced by routing, never changed by patch
+        outbound.tag = tag;
+    }
+    Ok(())
+}
+
+/// Build outbound of node with its overrides
+///
+/// ## Arguments
+///
+/// `node`: node info from subscription
+/// `rua`: overrides of node and subscription
+/// `tag`: outbound tag name
+pub fn node_outbound(node: &Node, rua: &RConfig, tag: String) -> Result<Outbound> {
+    let mut outbound = proxy_builder(node, tag)?;
+    apply_overrides(&mut outbound, &node_overrides(node, rua))
+        .map_err(|e| anyhow!("apply override of node {} failed {}", node.ps, e))?;
+    Ok(outbound)
+}
@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{fs::File, path::Path};
+
+/// User overlay file, applied after Venus generated the core config.
+/// Used to add things which Venus doesn't model.
+///
+/// ```json
+/// {
+///   "prepend": { "rules": [], "inbounds": [], "outbounds": [] },
+///   "append": { "rules": [], "inbounds": [], "outbounds": [] },
+///   "merge": { "log": { "loglevel": "debug" } }
+/// }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Overlay {
+    /// Items inserted before generated items
+    #[serde(default)]
+    pub prepend: OverlaySections,
+    /// Items inserted after generated items
+    #[serde(default)]
+    pub append: OverlaySections,
+    /// JSON merge patch (RFC 7386), applied after prepend and append
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge: Option<Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlaySections {
+    /// Items of `routing.rules`
+    #[serde(default)]
+    pub rules: Vec<Value>,
+    #[serde(default)]
+    pub inbounds: Vec<Value>,
+    #[serde(default)]
+    pub outbounds: Vec<Value>,
+}
+
+impl Overlay {
+    /// Read overlay from file, return empty overlay if file not exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = File::open(path)?;
+        let overlay = serde_json::from_reader(file)
+            .map_err(|e| anyhow!("parse overlay {} failed {}", path.display(), e))?;
+        Ok(overlay)
+    }
+
+    /// Apply overlay to generated core config
+    ///
+    /// ## Arguments
+    ///
+    /// `core`: generated core config json
+    pub fn apply(&self, core: &mut Value) -> Result<()> {
+        let sections = [
+            ("inbounds", &self.prepend.inbounds, &self.append.inbounds),
+            ("outbounds", &self.prepend.outbounds, &self.append.outbounds),
+            ("rules", &self.prepend.rules, &self.append.rules),
+        ];
+        for (name, prepend, append) in sections {
+            if prepend.is_empty() && append.is_empty() {
+                continue;
+            }
+            let list = match name {
+                "rules" => section_mut(core, &["routing", "rules"])?,
+                _ => section_mut(core, &[name])?,
+            };
+            list.splice(0..0, prepend.iter().cloned());
+            list.extend(append.iter().cloned());
+        }
+        if let Some(patch) = &self.merge {
+            merge_patch(core, patch);
+        }
+        Ok(())
+    }
+}
+
+/// Find array by keys, create it if not exist
+fn section_mut<'a>(core: &'a mut Value, keys: &[&str]) -> Result<&'a mut Vec<Value>> {
+    let mut current = core;
+    for key in keys {
+        let object = current
+            .as_object_mut()
+            .ok_or(anyhow!("core config {} is not an object", key))?;
+        current = object.entry(*key).or_insert(Value::Null);
+    }
+    if current.is_null() {
+        *current = Value::Array(vec![]);
+    }
+    current
+        .as_array_mut()
+        .ok_or(anyhow!("core config {} is not an array", keys.join(".")))
+}
+
+/// JSON merge patch (RFC 7386)
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::utils::{consts::NAME, file::move_path};
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Command line argument of custom config folder
+const CONFIG_DIR_ARG: &str = "--config-dir";
+/// Command line argument of portable mode
+const PORTABLE_ARG: &str = "--portable";
+/// Environment variable of custom config folder
+const CONFIG_DIR_ENV: &str = "VENUS_CONFIG_DIR";
+/// Portable mode is enabled when this file exists next to the executable
+const PORTABLE_MARKER: &str = "portable";
+
+/// How the folders are determined
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PathMode {
+    /// XDG base directories
+    Xdg,
+    /// `--config-dir` argument or `VENUS_CONFIG_DIR`
+    Custom,
+    /// Next to the executable
+    Portable,
+}
+
+impl Default for PathMode {
+    fn default() -> Self {
+        Self::Xdg
+    }
+}
+
+/// Folders used by Venus
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppPaths {
+    pub mode: PathMode,
+    /// `config.json`, `config.toml`, profiles and snapshots
+    pub config: PathBuf,
+    /// Downloaded geo data and rule sets
+    pub data: PathBuf,
+    /// Log files
+    pub state: PathBuf,
+}
+
+impl AppPaths {
+    /// Resolve folders, the priority is
+    /// `--config-dir`, `VENUS_CONFIG_DIR`, portable mode, then XDG base directories.
+    pub fn resolve() -> Result<Self> {
+        let args = env::args().collect::<Vec<_>>();
+        if let Some(dir) = arg_value(&args, CONFIG_DIR_ARG) {
+            return Ok(Self::single(PathMode::Custom, PathBuf::from(dir)));
+        }
+        if let Some(dir) = env::var_os(CONFIG_DIR_ENV).filter(|d| !d.is_empty()) {
+            return Ok(Self::single(PathMode::Custom, PathBuf::from(dir)));
+        }
+        let exe_dir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(PathBuf::from));
+        let portable = args.iter().any(|a| a == PORTABLE_ARG)
+            || exe_dir
+                .as_ref()
+                .map(|dir| dir.join(PORTABLE_MARKER).exists())
+                .unwrap_or(false);
+        let exe_dir = exe_dir.ok_or(anyhow!("cannot detect executable folder"));
+        if portable {
+            return Ok(Self::single(PathMode::Portable, exe_dir?.join("data")));
+        }
+
+        match home::home_dir() {
+            Some(home) => Ok(Self::xdg(&home)),
+            None => {
+                error!("Cannot detect user home folder, use portable mode instead");
+                Ok(Self::single(PathMode::Portable, exe_dir?.join("data")))
+            }
+        }
+    }
+
+    /// All files in one folder
+    fn single(mode: PathMode, dir: PathBuf) -> Self {
+        Self {
+            mode,
+            config: dir.clone(),
+            data: dir.clone(),
+            state: dir,
+        }
+    }
+
+    /// XDG base directories, data and state folders follow
+    /// the config folder on Windows and macOS unless XDG variables are set.
+    fn xdg(home: &Path) -> Self {
+        let config = xdg_dir("XDG_CONFIG_HOME", home, ".config");
+        let fallback = |var: &str, default: &str| {
+            if cfg!(any(target_os = "windows", target_os = "macos")) && env_dir(var).is_none() {
+                config.clone()
+            } else {
+                xdg_dir(var, home, default)
+            }
+        };
+        Self {
+            mode: PathMode::Xdg,
+            data: fallback("XDG_DATA_HOME", ".local/share"),
+            state: fallback("XDG_STATE_HOME", ".local/state"),
+            config,
+        }
+    }
+
+    /// Move files from old `~/.config/venus` layout
+    /// to current folders if they are not exist yet.
+    pub fn migrate_legacy(&self) {
+        if self.mode != PathMode::Xdg {
+            return;
+        }
+        let legacy = match home::home_dir() {
+            Some(home) => home.join(".config").join(NAME),
+            None => return,
+        };
+        if !legacy.exists() {
+            return;
+        }
+        let mut moves = vec![
+            (legacy.join("assets"), self.data.join("assets")),
+            (legacy.join("logs"), self.state.join("logs")),
+        ];
+        if legacy != self.config && !self.config.join("config.toml").exists() {
+            moves.extend(
+                [
+                    "config.json",
+                    "config.toml",
+                    "overlay.json",
+                    "profiles",
+                    "snapshots",
+                ]
+                .iter()
+                .map(|name| (legacy.join(name), self.config.join(name))),
+            );
+        }
+        for (from, to) in moves {
+            if from == to || !from.exists() || to.exists() {
+                continue;
+            }
+            match move_path(&from, &to) {
+                Ok(_) => info!("Moved {} to {}", from.display(), to.display()),
+                Err(err) => error!("Move {} to {} failed {}", from.display(), to.display(), err),
+            }
+        }
+    }
+}
+
+/// Value of `--name value` or `--name=value`
+fn arg_value(args: &[String], name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == name {
+            args.get(i + 1).cloned()
+        } else {
+            arg.strip_prefix(&prefix).map(String::from)
+        }
+    })
+}
+
+/// Absolute path in environment variable, relative paths are ignored by XDG spec
+fn env_dir(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
+fn xdg_dir(var: &str, home: &Path, default: &str) -> PathBuf {
+    env_dir(var)
+        .unwrap_or_else(|| home.join(default))
+        .join(NAME)
+}
@@ -0,0 +1,108 @@
+use super::{Dns, Overlay, Routing};
+use crate::utils::file::atomic_write;
+use anyhow::{anyhow, bail, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+/// Named set of routing, DNS and overlay settings with selected node,
+/// stored as `profiles/{name}.json`
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub routing: Routing,
+    #[serde(default)]
+    pub dns: Dns,
+    #[serde(default)]
+    pub overlay: Overlay,
+    /// Selected node id, keep current node if empty or not found
+    #[serde(default)]
+    pub node_id: String,
+}
+
+/// Profile name will be used as file name
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        bail!("profile name is empty");
+    }
+    if name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.'))
+    {
+        bail!(
+            "invalid profile name {}, only letters, numbers, space, '-', '_' and '.' are allowed",
+            name
+        );
+    }
+    Ok(())
+}
+
+fn profile_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+/// All profiles in profile folder, sorted by name
+pub fn list_profiles(dir: &Path) -> Result<Vec<Profile>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut profiles = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|e| e == "json").unwrap_or(false))
+        // one broken file should not hide other profiles
+        .filter_map(|path| {
+            let profile = File::open(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| {
+                    serde_json::from_reader::<_, Profile>(file).map_err(anyhow::Error::from)
+                });
+            profile
+                .map_err(|e| warn!("Skip profile {}, parse failed {}", path.display(), e))
+                .ok()
+        })
+        .collect::<Vec<_>>();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+pub fn profile_exists(dir: &Path, name: &str) -> Result<bool> {
+    Ok(profile_path(dir, name)?.exists())
+}
+
+pub fn load_profile(dir: &Path, name: &str) -> Result<Profile> {
+    let path = profile_path(dir, name)?;
+    if !path.exists() {
+        bail!("profile {} not found", name);
+    }
+    let file = File::open(&path)?;
+    let mut profile: Profile = serde_json::from_reader(file)
+        .map_err(|e| anyhow!("parse profile {} failed {}", path.display(), e))?;
+    // file name is the source of truth
+    profile.name = name.to_owned();
+    Ok(profile)
+}
+
+pub fn save_profile(dir: &Path, profile: &Profile) -> Result<()> {
+    let path = profile_path(dir, &profile.name)?;
+    fs::create_dir_all(dir)?;
+    let content = serde_json::to_string_pretty(profile)?;
+    atomic_write(&path, content.as_bytes())?;
+    Ok(())
+}
+
+pub fn delete_profile(dir: &Path, name: &str) -> Result<()> {
+    let path = profile_path(dir, name)?;
+    if !path.exists() {
+        bail!("profile {} not found", name);
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
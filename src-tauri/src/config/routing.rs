@@ -0,0 +1,185 @@
+use super::{
+    matcher::{validate_domain, validate_ip},
+    Port, ProxyMode, Rule,
+};
+use serde_json::Value;
+
+const NETWORKS: [&str; 3] = ["tcp", "udp", "tcp,udp"];
+
+/// Tags which can be referenced by routing rules
+#[derive(Debug, Default, Clone)]
+pub struct RouteTargets {
+    pub outbounds: Vec<String>,
+    pub balancers: Vec<String>,
+}
+
+impl RouteTargets {
+    /// Collect tags from runtime config, so outbounds added by overlay are included
+    pub fn from_runtime(runtime: &Value) -> Self {
+        let tags = |list: Option<&Value>| {
+            list.and_then(|l| l.as_array())
+                .map(|l| {
+                    l.iter()
+                        .filter_map(|item| item.get("tag")?.as_str().map(String::from))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        };
+        Self {
+            outbounds: tags(runtime.get("outbounds")),
+            balancers: tags(runtime.get("routing").and_then(|r| r.get("balancers"))),
+        }
+    }
+}
+
+/// Rules used by Venus itself, kept in every mode.
+/// The speed test rule, and the stats api rule which routes `api` inbound to `api` outbound.
+fn is_system_rule(rule: &Rule) -> bool {
+    let api_rule = rule.outbound_tag == "api"
+        && rule
+            .inbound_tag
+            .as_ref()
+            .map(|tags| tags.iter().all(|tag| tag == "api"))
+            .unwrap_or(false);
+    rule.outbound_tag == "speed" || api_rule
+}
+
+/// Generate effective rules of routing mode, user rules are not changed
+///
+/// ## Arguments
+///
+/// `mode`: routing mode
+/// `rules`: enabled rules in core config
+pub fn mode_rules(mode: ProxyMode, rules: &[Rule]) -> Vec<Rule> {
+    let catch_all = |outbound: &str| Rule {
+        network: Some("tcp,udp".into()),
+        ..Rule::new(outbound.into())
+    };
+    match mode {
+        ProxyMode::Rule => rules.to_vec(),
+        ProxyMode::Global => {
+            let mut result = rules
+                .iter()
+                .filter(|r| is_system_rule(r))
+                .cloned()
+                .collect::<Vec<_>>();
+            result.push(Rule {
+                ip: Some(vec!["geoip:private".into()]),
+                ..Rule::new("direct".into())
+            });
+            result.push(catch_all("proxy"));
+            result
+        }
+        ProxyMode::Direct => {
+            let mut result = rules
+                .iter()
+                .filter(|r| is_system_rule(r))
+                .cloned()
+                .collect::<Vec<_>>();
+            result.push(catch_all("direct"));
+            result
+        }
+    }
+}
+
+/// Validate port like "53", "1000-2000" or "53,443,1000-2000"
+fn validate_port(port: &Port) -> Result<(), String> {
+    let range = match port {
+        Port::Number(_) => return Ok(()),
+        Port::Range(range) => range,
+    };
+    let valid = range.split(',').all(|part| {
+        let mut bounds = part.trim().splitn(2, '-').map(|p| p.trim().parse::<u16>());
+        match (bounds.next(), bounds.next()) {
+            (Some(Ok(_)), None) => true,
+            (Some(Ok(start)), Some(Ok(end))) => start <= end,
+            _ => false,
+        }
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid port {}", range))
+    }
+}
+
+/// Validate routing rule
+///
+/// ## Arguments
+///
+/// `rule`: the rule to be validated
+/// `targets`: outbound and balancer tags in core config
+///
+/// ## Return
+///
+/// All problems found in rule
+pub fn validate_rule(rule: &Rule, targets: &RouteTargets) -> Vec<String> {
+    let mut errors = vec![];
+    if rule.type_field != "field" {
+        errors.push(format!(
+            "invalid rule type {}, expect field",
+            rule.type_field
+        ));
+    }
+
+    match (rule.outbound_tag.is_empty(), &rule.balancer_tag) {
+        (true, None) => errors.push("rule needs outbound tag or balancer tag".into()),
+        (false, Some(_)) => {
+            errors.push("rule can not have both outbound tag and balancer tag".into())
+        }
+        (false, None) if !targets.outbounds.contains(&rule.outbound_tag) => {
+            errors.push(format!("outbound {} not found", rule.outbound_tag))
+        }
+        (true, Some(tag)) if !targets.balancers.contains(tag) => {
+            errors.push(format!("balancer {} not found", tag))
+        }
+        _ => {}
+    }
+
+    let has_condition = rule.domain.is_some()
+        || rule.ip.is_some()
+        || rule.port.is_some()
+        || rule.network.is_some()
+        || rule.source.is_some()
+        || rule.user.is_some()
+        || rule.inbound_tag.is_some()
+        || rule.protocol.is_some()
+        || rule.attrs.is_some()
+        || !rule.extra.is_empty();
+    if !has_condition {
+        errors.push("rule has no condition".into());
+    }
+
+    let mut push = |result: anyhow::Result<()>| {
+        if let Err(err) = result {
+            errors.push(err.to_string());
+        }
+    };
+    rule.domain
+        .iter()
+        .flatten()
+        .for_each(|d| push(validate_domain(d)));
+    rule.ip
+        .iter()
+        .flatten()
+        .for_each(|ip| push(validate_ip(ip)));
+    rule.source
+        .iter()
+        .flatten()
+        .for_each(|ip| push(validate_ip(ip)));
+
+    if let Some(Err(err)) = rule.port.as_ref().map(validate_port) {
+        errors.push(err);
+    }
+    if let Some(network) = &rule.network {
+        let network = network.replace(' ', "");
+        if !NETWORKS.contains(&network.as_str()) {
+            errors.push(format!(
+                "invalid network {}, expect one of {}",
+                network,
+                NETWORKS.join(", ")
+            ));
+        }
+    }
+    errors
+}
@@ -0,0 +1,232 @@
+use super::{
+    dns::parse_hosts,
+    matcher::{validate_domain, validate_ip},
+    Rule, RuleSet, RuleSetFormat,
+};
+use crate::utils::{
+    consts::{NAME, VERSION},
+    file::atomic_write,
+};
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose, Engine};
+use chrono::Local;
+use log::warn;
+use reqwest::header::USER_AGENT;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Parsed entries of rule set
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSetEntries {
+    pub domains: Vec<String>,
+    pub ips: Vec<String>,
+}
+
+impl RuleSetEntries {
+    /// Add domain or IP, invalid entries are ignored
+    fn push(&mut self, entry: &str) {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            return;
+        }
+        let is_ip = entry
+            .split('/')
+            .next()
+            .map(|ip| ip.parse::<IpAddr>().is_ok())
+            .unwrap_or(false);
+        if is_ip {
+            if validate_ip(entry).is_ok() {
+                self.ips.push(entry.to_owned());
+            }
+            return;
+        }
+        let domain = if entry.contains(':') {
+            entry.to_owned()
+        } else {
+            format!("domain:{}", entry.trim_start_matches('.'))
+        };
+        if validate_domain(&domain).is_ok() && !self.domains.contains(&domain) {
+            self.domains.push(domain);
+        }
+    }
+}
+
+/// Rule set name will be used as file name
+pub fn validate_rule_set(rule_set: &RuleSet) -> Result<()> {
+    let name = &rule_set.name;
+    if name.trim().is_empty() {
+        bail!("rule set name is empty");
+    }
+    if name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.'))
+    {
+        bail!("invalid rule set name {}", name);
+    }
+    Url::parse(&rule_set.url)
+        .map_err(|e| anyhow!("invalid rule set url {} {}", rule_set.url, e))?;
+    if rule_set.outbound_tag.is_empty() {
+        bail!("outbound tag of rule set {} is empty", name);
+    }
+    Ok(())
+}
+
+fn entries_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.json", name))
+}
+
+/// Parse rule list content by format
+pub fn parse_rule_set(content: &str, format: RuleSetFormat) -> Result<RuleSetEntries> {
+    let mut entries = RuleSetEntries::default();
+    match format {
+        RuleSetFormat::Domains => content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default())
+            .for_each(|line| entries.push(line)),
+        RuleSetFormat::Hosts => parse_hosts(content)
+            .keys()
+            .for_each(|domain| entries.push(domain)),
+        RuleSetFormat::AutoProxy => {
+            let content = content
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>();
+            let content = general_purpose::STANDARD.decode(content)?;
+            String::from_utf8_lossy(&content)
+                .lines()
+                .filter_map(auto_proxy_entry)
+                .for_each(|entry| entries.push(&entry));
+        }
+    }
+    Ok(entries)
+}
+
+/// Convert AutoProxy line to domain matcher,
+/// comments, exceptions and wildcard patterns are skipped
+fn auto_proxy_entry(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with(['!', '[']) || line.starts_with("@@") {
+        return None;
+    }
+    if line.len() > 2 && line.starts_with('/') && line.ends_with('/') {
+        return Some(format!("regexp:{}", &line[1..line.len() - 1]));
+    }
+    let host = if let Some(domain) = line.strip_prefix("||") {
+        domain
+    } else if let Some(url) = line.strip_prefix('|') {
+        url.split_once("://").map(|(_, rest)| rest).unwrap_or(url)
+    } else {
+        line.split_once("://").map(|(_, rest)| rest).unwrap_or(line)
+    };
+    let host = host
+        .split(['/', '^', ':'])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('.');
+    if host.is_empty() || host.contains('*') || !host.contains('.') {
+        return None;
+    }
+    Some(host.to_owned())
+}
+
+/// Download and parse rule set, config is not touched so it can be done without lock
+///
+/// ## Arguments
+///
+/// `rule_set`: rule set to be downloaded
+pub async fn download_rule_set(rule_set: &RuleSet) -> Result<RuleSetEntries> {
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let content = client
+        .get(&rule_set.url)
+        .header(USER_AGENT, format!("{}/{}", NAME, VERSION))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let entries = parse_rule_set(&content, rule_set.format)?;
+    if entries.domains.is_empty() && entries.ips.is_empty() {
+        bail!("rule set {} has no valid entry", rule_set.name);
+    }
+    Ok(entries)
+}
+
+/// Save downloaded entries of rule set
+///
+/// ## Arguments
+///
+/// `dir`: rule set folder
+/// `rule_set`: rule set to be updated, counts and update time will be changed
+/// `entries`: downloaded entries
+///
+/// ## Return
+///
+/// Whether the entries are changed
+pub fn save_entries(dir: &Path, rule_set: &mut RuleSet, entries: &RuleSetEntries) -> Result<bool> {
+    let changed = load_entries(dir, &rule_set.name)
+        .map(|old| &old != entries)
+        .unwrap_or(true);
+    let content = serde_json::to_vec(entries)?;
+    atomic_write(&entries_path(dir, &rule_set.name), &content)?;
+
+    rule_set.domain_count = entries.domains.len();
+    rule_set.ip_count = entries.ips.len();
+    rule_set.updated_at = Some(Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+    rule_set.error = None;
+    Ok(changed)
+}
+
+pub fn load_entries(dir: &Path, name: &str) -> Result<RuleSetEntries> {
+    let file = File::open(entries_path(dir, name))?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+pub fn remove_entries(dir: &Path, name: &str) -> Result<()> {
+    let path = entries_path(dir, name);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Compile enabled rule sets into routing rules
+///
+/// ## Arguments
+///
+/// `dir`: rule set folder
+/// `rule_sets`: rule sets in rua config
+pub fn rule_set_rules(dir: &Path, rule_sets: &[RuleSet]) -> Vec<Rule> {
+    rule_sets
+        .iter()
+        .filter(|rule_set| !rule_set.disabled)
+        .filter_map(|rule_set| {
+            load_entries(dir, &rule_set.name)
+                .map_err(|e| warn!("Load rule set {} failed {}", rule_set.name, e))
+                .ok()
+                .map(|entries| (rule_set, entries))
+        })
+        .flat_map(|(rule_set, entries)| {
+            let mut rules = vec![];
+            if !entries.domains.is_empty() {
+                rules.push(Rule {
+                    domain: Some(entries.domains),
+                    ..Rule::new(rule_set.outbound_tag.clone())
+                });
+            }
+            if !entries.ips.is_empty() {
+                rules.push(Rule {
+                    ip: Some(entries.ips),
+                    ..Rule::new(rule_set.outbound_tag.clone())
+                });
+            }
+            rules
+        })
+        .collect()
+}
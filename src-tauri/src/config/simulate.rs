@@ -0,0 +1,599 @@
+use super::{Port, Rule};
+use crate::geo::{
+    asset_file,
+    dat::{load_geoip, load_geosite, Cidr, GeoIp, GeoSite},
+    GEOIP, GEOSITE,
+};
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fs, net::IpAddr};
+use tokio::net::lookup_host;
+
+/// Fields of rule which are not conditions
+const NON_CONDITION_KEYS: [&str; 2] = ["ruleTag", "domainMatcher"];
+
+/// Destination to be routed
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteRequest {
+    /// Domain or IP
+    pub destination: String,
+    pub port: Option<u16>,
+    /// "tcp" or "udp", default is "tcp"
+    pub network: Option<String>,
+    pub inbound_tag: Option<String>,
+    /// Source IP
+    pub source: Option<String>,
+    /// Resolve domain with system DNS when domain strategy needs IP
+    #[serde(default)]
+    pub resolve: bool,
+}
+
+/// Which outbound the destination would use
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteResult {
+    /// Index of matched rule in runtime config
+    pub index: Option<usize>,
+    pub rule: Option<Value>,
+    pub outbound_tag: Option<String>,
+    pub balancer_tag: Option<String>,
+    pub domain_strategy: String,
+    pub resolved_ips: Vec<String>,
+    /// How the result is decided
+    pub steps: Vec<String>,
+}
+
+/// Loaded geo data files and codes
+#[derive(Default)]
+struct GeoCache {
+    files: HashMap<String, Vec<u8>>,
+    sites: HashMap<String, Option<GeoSite>>,
+    ips: HashMap<String, Option<GeoIp>>,
+}
+
+impl GeoCache {
+    fn file(&mut self, name: &str) -> Result<&[u8]> {
+        if !self.files.contains_key(name) {
+            let data = fs::read(asset_file(name))
+                .map_err(|e| anyhow!("read geo data {} failed {}", name, e))?;
+            self.files.insert(name.to_owned(), data);
+        }
+        Ok(&self.files[name])
+    }
+
+    fn geosite(&mut self, file: &str, code: &str) -> Result<Option<&GeoSite>> {
+        let key = format!("{}:{}", file, code);
+        if !self.sites.contains_key(&key) {
+            let site = load_geosite(self.file(file)?, code)?;
+            self.sites.insert(key.clone(), site);
+        }
+        Ok(self.sites[&key].as_ref())
+    }
+
+    fn geoip(&mut self, file: &str, code: &str) -> Result<Option<&GeoIp>> {
+        let key = format!("{}:{}", file, code);
+        if !self.ips.contains_key(&key) {
+            let geoip = load_geoip(self.file(file)?, code)?;
+            self.ips.insert(key.clone(), geoip);
+        }
+        Ok(self.ips[&key].as_ref())
+    }
+
+    /// Test domain with geosite code like `google@cn`
+    fn match_site(&mut self, file: &str, code: &str, domain: &str) -> Result<Option<String>> {
+        let (code, attribute) = match code.split_once('@') {
+            Some((code, attribute)) => (code, Some(attribute)),
+            None => (code, None),
+        };
+        let site = self
+            .geosite(file, code)?
+            .ok_or(anyhow!("{} not found in {}", code, file))?;
+        Ok(site.find(domain, attribute).map(|d| d.matcher()))
+    }
+
+    /// Test IP with geoip code
+    fn match_geoip(&mut self, file: &str, code: &str, ip: &IpAddr) -> Result<bool> {
+        let geoip = self
+            .geoip(file, code)?
+            .ok_or(anyhow!("{} not found in {}", code, file))?;
+        Ok(geoip.find(ip).is_some() != geoip.reverse_match)
+    }
+}
+
+/// Test domain with matcher
+///
+/// ## Return
+///
+/// Description of matched entry, None if not matched
+fn match_domain(cache: &mut GeoCache, matcher: &str, domain: &str) -> Result<Option<String>> {
+    let matched = match matcher.split_once(':') {
+        None => !matcher.is_empty() && domain.contains(matcher),
+        Some(("domain", value)) => {
+            domain == value
+                || domain
+                    .strip_suffix(value)
+                    .map(|p| p.ends_with('.'))
+                    .unwrap_or(false)
+        }
+        Some(("full", value)) => domain == value,
+        Some(("keyword", value)) => !value.is_empty() && domain.contains(value),
+        Some(("dotless", value)) => !domain.contains('.') && domain.contains(value),
+        Some(("regexp", value)) => Regex::new(value)?.is_match(domain),
+        Some(("geosite", code)) => {
+            return Ok(cache
+                .match_site(GEOSITE, code, domain)?
+                .map(|entry| format!("{} ({})", matcher, entry)))
+        }
+        Some(("ext", ext)) => {
+            let (file, code) = ext
+                .split_once(':')
+                .ok_or(anyhow!("invalid matcher {}", matcher))?;
+            return Ok(cache
+                .match_site(file, code, domain)?
+                .map(|entry| format!("{} ({})", matcher, entry)));
+        }
+        Some(_) => false,
+    };
+    Ok(if matched {
+        Some(matcher.to_owned())
+    } else {
+        None
+    })
+}
+
+/// Test IP with matcher
+fn match_ip(cache: &mut GeoCache, matcher: &str, ip: &IpAddr) -> Result<bool> {
+    if let Some(code) = matcher.strip_prefix("geoip:") {
+        return match code.strip_prefix('!') {
+            Some(code) => Ok(!cache.match_geoip(GEOIP, code, ip)?),
+            None => cache.match_geoip(GEOIP, code, ip),
+        };
+    }
+    if let Some(ext) = matcher.strip_prefix("ext:") {
+        let (file, code) = ext
+            .split_once(':')
+            .ok_or(anyhow!("invalid matcher {}", matcher))?;
+        return cache.match_geoip(file, code, ip);
+    }
+    let (net, prefix) = match matcher.split_once('/') {
+        Some((net, prefix)) => (net.parse::<IpAddr>()?, prefix.parse::<u8>()?),
+        None => {
+            let net = matcher.parse::<IpAddr>()?;
+            (net, if net.is_ipv4() { 32 } else { 128 })
+        }
+    };
+    Ok(Cidr { ip: net, prefix }.contains(ip))
+}
+
+fn match_port(port: &Port, target: u16) -> bool {
+    match port {
+        Port::Number(port) => *port == target,
+        Port::Range(range) => range.split(',').any(|part| {
+            let mut bounds = part.trim().splitn(2, '-').map(|p| p.trim().parse::<u16>());
+            match (bounds.next(), bounds.next()) {
+                (Some(Ok(port)), None) => port == target,
+                (Some(Ok(start)), Some(Ok(end))) => (start..=end).contains(&target),
+                _ => false,
+            }
+        }),
+    }
+}
+
+/// Destination info used in matching
+struct Target<'a> {
+    request: &'a RouteRequest,
+    domain: Option<&'a str>,
+    ips: &'a [IpAddr],
+}
+
+/// Test all conditions of rule, conditions which can not be simulated
+/// are errors if other conditions are matched
+///
+/// ## Return
+///
+/// Reason of match or mismatch
+fn match_rule(cache: &mut GeoCache, rule: &Rule, target: &Target) -> Result<(bool, String)> {
+    let mut reasons = vec![];
+    if let Some(domains) = &rule.domain {
+        let domain = match target.domain {
+            Some(domain) => domain,
+            None => return Ok((false, "domain rule does not match IP destination".into())),
+        };
+        let mut matched = None;
+        for matcher in domains {
+            if let Some(m) = match_domain(cache, matcher, domain)? {
+                matched = Some(m);
+                break;
+            }
+        }
+        match matched {
+            Some(m) => reasons.push(format!("domain matched {}", m)),
+            None => return Ok((false, "domain not matched".into())),
+        }
+    }
+    if let Some(ips) = &rule.ip {
+        if target.ips.is_empty() {
+            return Ok((false, "no IP to match ip condition".into()));
+        }
+        let mut matched = None;
+        'outer: for ip in target.ips {
+            for matcher in ips {
+                if match_ip(cache, matcher, ip)? {
+                    matched = Some(format!("ip {} matched {}", ip, matcher));
+                    break 'outer;
+                }
+            }
+        }
+        match matched {
+            Some(m) => reasons.push(m),
+            None => return Ok((false, "ip not matched".into())),
+        }
+    }
+    if let Some(port) = &rule.port {
+        match target.request.port {
+            Some(p) if match_port(port, p) => reasons.push(format!("port {} matched {}", p, port)),
+            _ => return Ok((false, format!("port not matched {}", port))),
+        }
+    }
+    if let Some(network) = &rule.network {
+        let request_network = target.request.network.as_deref().unwrap_or("tcp");
+        if !network.split(',').any(|n| n.trim() == request_network) {
+            return Ok((false, format!("network not matched {}", network)));
+        }
+        reasons.push(format!("network matched {}", network));
+    }
+    if let Some(sources) = &rule.source {
+        let source = match target.request.source.as_deref().map(str::parse::<IpAddr>) {
+            Some(Ok(source)) => source,
+            _ => return Ok((false, "no source IP to match source condition".into())),
+        };
+        let mut matched = false;
+        for matcher in sources {
+            if match_ip(cache, matcher, &source)? {
+                matched = true;
+                break;
+            }
+        }
+        if !matched {
+            return Ok((false, "source not matched".into()));
+        }
+        reasons.push(format!("source {} matched", source));
+    }
+    if let Some(tags) = &rule.inbound_tag {
+        match &target.request.inbound_tag {
+            Some(tag) if tags.contains(tag) => reasons.push(format!("inbound {} matched", tag)),
+            _ => return Ok((false, "inbound tag not matched".into())),
+        }
+    }
+    if rule.user.is_some() || rule.protocol.is_some() || rule.attrs.is_some() {
+        bail!("user, protocol and attrs can not be simulated");
+    }
+    if let Some(key) = rule
+        .extra
+        .keys()
+        .find(|k| !NON_CONDITION_KEYS.contains(&k.as_str()))
+    {
+        bail!("condition {} can not be simulated", key);
+    }
+    Ok((true, reasons.join(", ")))
+}
+
+/// Evaluate rules in order, return the first matched rule.
+/// Rules which can not be checked or simulated are skipped and reported in steps.
+fn first_match(
+    cache: &mut GeoCache,
+    rules: &[Rule],
+    target: &Target,
+    steps: &mut Vec<String>,
+) -> Option<usize> {
+    for (index, rule) in rules.iter().enumerate() {
+        // like missing geo code or user condition
+        let (matched, reason) = match match_rule(cache, rule, target) {
+            Ok(result) => result,
+            Err(err) => {
+                steps.push(format!("rule {} can not be checked: {}", index, err));
+                continue;
+            }
+        };
+        if matched {
+            steps.push(format!("rule {} matched: {}", index, reason));
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// Find which rule and outbound the destination would use
+///
+/// ## Arguments
+///
+/// `runtime`: the config which core runs with
+/// `request`: destination to be routed
+pub async fn simulate_route(runtime: &Value, request: &RouteRequest) -> Result<RouteResult> {
+    let routing = runtime.get("routing").cloned().unwrap_or_default();
+    let rules: Vec<Rule> = routing
+        .get("rules")
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()?
+        .unwrap_or_default();
+    let strategy = routing
+        .get("domainStrategy")
+        .and_then(|s| s.as_str())
+        .unwrap_or("AsIs")
+        .to_owned();
+
+    // domains in geo data and rules are lowercase
+    let destination = request.destination.trim().to_lowercase();
+    let destination = destination.as_str();
+    let mut result = RouteResult {
+        domain_strategy: strategy.clone(),
+        ..Default::default()
+    };
+    let steps = &mut result.steps;
+    let (domain, mut ips) = match destination.parse::<IpAddr>() {
+        Ok(ip) => (None, vec![ip]),
+        Err(_) => (Some(destination), vec![]),
+    };
+
+    let resolved = if domain.is_some() && strategy != "AsIs" && request.resolve {
+        let host = (destination, request.port.unwrap_or(0));
+        let ips = lookup_host(host)
+            .await
+            .map(|addrs| addrs.map(|a| a.ip()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        steps.push(format!(
+            "resolved {} to {} with system DNS, core may get different IPs from its own DNS",
+            destination,
+            ips.iter()
+                .map(|ip| ip.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        ips
+    } else {
+        vec![]
+    };
+    result.resolved_ips = resolved.iter().map(|ip| ip.to_string()).collect();
+
+    let mut cache = GeoCache::default();
+    let matched = match strategy.as_str() {
+        "IPOnDemand" if domain.is_some() => {
+            steps.push(
+                "IPOnDemand: domain is resolved when any IP rule is checked, so IP rules match resolved IPs".into(),
+            );
+            ips.extend(resolved.iter());
+            let target = Target {
+                request,
+                domain,
+                ips: &ips,
+            };
+            first_match(&mut cache, &rules, &target, steps)
+        }
+        "IPIfNonMatch" if domain.is_some() => {
+            steps.push(
+                "IPIfNonMatch: match with domain first, resolve and match again with IPs if no rule matched".into(),
+            );
+            let target = Target {
+                request,
+                domain,
+                ips: &ips,
+            };
+            match first_match(&mut cache, &rules, &target, steps) {
+                Some(index) => Some(index),
+                None if !resolved.is_empty() => {
+                    steps.push("no rule matched domain, match again with resolved IPs".into());
+                    let target = Target {
+                        request,
+                        domain,
+                        ips: &resolved,
+                    };
+                    first_match(&mut cache, &rules, &target, steps)
+                }
+                None => None,
+            }
+        }
+        _ => {
+            if domain.is_some() {
+                steps.push("AsIs: only domain is used, IP rules never match a domain".into());
+            }
+            let target = Target {
+                request,
+                domain,
+                ips: &ips,
+            };
+            first_match(&mut cache, &rules, &target, steps)
+        }
+    };
+
+    match matched {
+        Some(index) => {
+            let rule = &rules[index];
+            result.index = Some(index);
+            result.rule = Some(serde_json::to_value(rule)?);
+            if let Some(balancer) = &rule.balancer_tag {
+                result.balancer_tag = Some(balancer.clone());
+            } else {
+                result.outbound_tag = Some(rule.outbound_tag.clone());
+            }
+        }
+        None => {
+            let default = runtime
+                .get("outbounds")
+                .and_then(|o| o.get(0))
+                .and_then(|o| o.get("tag"))
+                .and_then(|t| t.as_str())
+                .map(String::from);
+            result.steps.push(format!(
+                "no rule matched, use the first outbound {}",
+                default.as_deref().unwrap_or("")
+            ));
+            result.outbound_tag = default;
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn runtime(strategy: &str, rules: Value) -> Value {
+        json!({
+            "outbounds": [{ "tag": "proxy" }, { "tag": "direct" }, { "tag": "block" }],
+            "routing": { "domainStrategy": strategy, "rules": rules }
+        })
+    }
+
+    fn request(destination: &str) -> RouteRequest {
+        RouteRequest {
+            destination: destination.into(),
+            port: Some(443),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn first_match_wins() {
+        let runtime = runtime(
+            "AsIs",
+            json!([
+                { "type": "field", "domain": ["full:www.example.com"], "outboundTag": "block" },
+                { "type": "field", "domain": ["domain:example.com"], "outboundTag": "direct" },
+                { "type": "field", "port": "1-65535", "outboundTag": "proxy" }
+            ]),
+        );
+        let result = simulate_route(&runtime, &request("Mail.Example.com"))
+            .await
+            .unwrap();
+        assert_eq!(result.index, Some(1));
+        assert_eq!(result.outbound_tag.as_deref(), Some("direct"));
+        assert_eq!(
+            result.steps.last().map(String::as_str),
+            Some("rule 1 matched: domain matched domain:example.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn use_first_outbound_if_no_rule_matched() {
+        let runtime = runtime(
+            "AsIs",
+            json!([{ "type": "field", "network": "udp", "outboundTag": "direct" }]),
+        );
+        let result = simulate_route(&runtime, &request("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(result.index, None);
+        assert_eq!(result.outbound_tag.as_deref(), Some("proxy"));
+    }
+
+    #[tokio::test]
+    async fn ip_rules_by_domain_strategy() {
+        let rules = json!([
+            { "type": "field", "ip": ["127.0.0.0/8"], "outboundTag": "direct" },
+            { "type": "field", "domain": ["keyword:local"], "balancerTag": "auto" }
+        ]);
+        let mut request = request("localhost");
+        request.resolve = true;
+
+        // localhost is resolved by hosts file, IP rules never match a domain in AsIs
+        let result = simulate_route(&runtime("AsIs", rules.clone()), &request)
+            .await
+            .unwrap();
+        assert!(result.resolved_ips.is_empty());
+        assert_eq!(result.index, Some(1));
+        assert_eq!(result.balancer_tag.as_deref(), Some("auto"));
+        assert_eq!(result.outbound_tag, None);
+
+        // domain matched first, resolved IPs are not used
+        let result = simulate_route(&runtime("IPIfNonMatch", rules.clone()), &request)
+            .await
+            .unwrap();
+        assert_eq!(result.index, Some(1));
+
+        // resolved IPs are matched by IP rules in order
+        let result = simulate_route(&runtime("IPOnDemand", rules.clone()), &request)
+            .await
+            .unwrap();
+        assert_eq!(result.domain_strategy, "IPOnDemand");
+        assert_eq!(result.index, Some(0));
+        assert_eq!(result.outbound_tag.as_deref(), Some("direct"));
+
+        // domain is not resolved if not asked
+        request.resolve = false;
+        let result = simulate_route(&runtime("IPOnDemand", rules), &request)
+            .await
+            .unwrap();
+        assert!(result.resolved_ips.is_empty());
+        assert_eq!(result.index, Some(1));
+    }
+
+    #[tokio::test]
+    async fn match_again_with_resolved_ips() {
+        let rules = json!([
+            { "type": "field", "domain": ["full:example.com"], "outboundTag": "block" },
+            { "type": "field", "ip": ["127.0.0.0/8", "::1"], "outboundTag": "direct" }
+        ]);
+        let mut request = request("localhost");
+        request.resolve = true;
+        let result = simulate_route(&runtime("IPIfNonMatch", rules), &request)
+            .await
+            .unwrap();
+        assert!(result
+            .steps
+            .iter()
+            .any(|s| s == "no rule matched domain, match again with resolved IPs"));
+        assert_eq!(result.index, Some(1));
+    }
+
+    #[tokio::test]
+    async fn report_skipped_rules() {
+        let runtime = runtime(
+            "AsIs",
+            json!([
+                { "type": "field", "domain": ["example.com"], "protocol": ["bittorrent"], "outboundTag": "block" },
+                { "type": "field", "domain": ["example.com"], "process": ["curl"], "outboundTag": "block" },
+                { "type": "field", "domain": ["regexp:("], "outboundTag": "block" },
+                { "type": "field", "domain": ["example.com"], "ruleTag": "web", "outboundTag": "direct" }
+            ]),
+        );
+        let result = simulate_route(&runtime, &request("example.com"))
+            .await
+            .unwrap();
+        assert_eq!(result.index, Some(3));
+        let skipped = result
+            .steps
+            .iter()
+            .filter(|s| s.contains("can not be checked"))
+            .collect::<Vec<_>>();
+        assert_eq!(skipped.len(), 3, "{:?}", result.steps);
+        assert!(skipped[0].contains("user, protocol and attrs can not be simulated"));
+        assert!(skipped[1].contains("condition process can not be simulated"));
+        assert!(skipped[2].starts_with("rule 2 "));
+    }
+
+    #[tokio::test]
+    async fn match_ip_destination() {
+        let runtime = runtime(
+            "IPIfNonMatch",
+            json!([
+                { "type": "field", "domain": ["domain:example.com"], "outboundTag": "block" },
+                { "type": "field", "ip": ["10.0.0.0/8"], "port": "80,443", "network": "tcp", "outboundTag": "direct" }
+            ]),
+        );
+        let result = simulate_route(&runtime, &request("10.1.2.3"))
+            .await
+            .unwrap();
+        assert_eq!(result.index, Some(1));
+        assert_eq!(result.outbound_tag.as_deref(), Some("direct"));
+
+        let mut udp = request("10.1.2.3");
+        udp.network = Some("udp".into());
+        let result = simulate_route(&runtime, &udp).await.unwrap();
+        assert_eq!(result.index, None);
+    }
+}
@@ -0,0 +1,319 @@
+use crate::utils::file::atomic_write;
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, NaiveDateTime};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Max snapshots kept for each config file
+const SNAPSHOT_LIMIT: usize = 20;
+/// Writes within this interval after last snapshot will not create new one,
+/// avoid frequent writes like speed test push out useful snapshots
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+/// Snapshots taken by older versions have no milliseconds
+const LEGACY_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Snapshot of `config.json` or `config.toml`,
+/// stored as `snapshots/{file}.{time}`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    /// Snapshot file name
+    pub name: String,
+    /// The config file name which snapshot belongs to
+    pub file: String,
+    pub created_at: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DiffTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Single line of diff between snapshot and current file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    pub content: String,
+}
+
+/// Copy current config file to snapshot folder before it's overwritten.
+/// Skip if content not changed or last snapshot is too new.
+///
+/// ## Arguments
+///
+/// `dir`: snapshot folder
+/// `path`: config file path
+/// `new_content`: the content will be written to config file
+pub fn take_snapshot(dir: &Path, path: &Path, new_content: &[u8]) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let current = fs::read(path)?;
+    if current == new_content {
+        return Ok(());
+    }
+    let file = file_name(path)?;
+    let snapshots = list_snapshots(dir, &[path])?;
+    let recent = snapshots
+        .first()
+        .and_then(|s| fs::metadata(dir.join(&s.name)).ok())
+        .and_then(|meta| meta.modified().ok())
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|elapsed| elapsed < SNAPSHOT_INTERVAL)
+        .unwrap_or(false);
+    if recent {
+        return Ok(());
+    }
+    save_snapshot(dir, &file, &current)?;
+    Ok(())
+}
+
+/// Copy current config file to snapshot folder whenever it's changed recently or not,
+/// used before the file is replaced by import or restore.
+///
+/// ## Arguments
+///
+/// `dir`: snapshot folder
+/// `path`: config file path
+///
+/// ## Return
+///
+/// Snapshot name, `None` if config file not exist
+pub fn force_snapshot(dir: &Path, path: &Path) -> Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let current = fs::read(path)?;
+    let name = save_snapshot(dir, &file_name(path)?, &current)?;
+    Ok(Some(name))
+}
+
+/// Write snapshot with unique name, and remove snapshots out of limit
+fn save_snapshot(dir: &Path, file: &str, content: &[u8]) -> Result<String> {
+    fs::create_dir_all(dir)?;
+    let mut time = Local::now().naive_local();
+    let mut name = format!("{}.{}", file, time.format(TIME_FORMAT));
+    // snapshots taken within same millisecond
+    while dir.join(&name).exists() {
+        time += chrono::Duration::milliseconds(1);
+        name = format!("{}.{}", file, time.format(TIME_FORMAT));
+    }
+    atomic_write(&dir.join(&name), content)?;
+
+    let snapshots = list_snapshots(dir, &[Path::new(file)])?;
+    snapshots.iter().skip(SNAPSHOT_LIMIT).for_each(|s| {
+        let _ = fs::remove_file(dir.join(&s.name));
+    });
+    Ok(name)
+}
+
+fn parse_time(time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(time, TIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(time, LEGACY_TIME_FORMAT))
+        .ok()
+}
+
+/// List snapshots of config files, newest first
+///
+/// ## Arguments
+///
+/// `dir`: snapshot folder
+/// `paths`: config files
+pub fn list_snapshots(dir: &Path, paths: &[&Path]) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let files = paths
+        .iter()
+        .map(|p| file_name(p))
+        .collect::<Result<Vec<_>>>()?;
+    let mut snapshots = fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (file, time) = name.rsplit_once('.')?;
+            if !files.iter().any(|f| f == file) {
+                return None;
+            }
+            let time = parse_time(time)?;
+            let snapshot = Snapshot {
+                file: file.to_owned(),
+                created_at: time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                name,
+            };
+            Some((time, snapshot))
+        })
+        .collect::<Vec<_>>();
+    snapshots.sort_by_key(|(time, _)| std::cmp::Reverse(*time));
+    Ok(snapshots.into_iter().map(|(_, s)| s).collect())
+}
+
+/// Find the config file which snapshot belongs to
+///
+/// ## Arguments
+///
+/// `dir`: snapshot folder
+/// `name`: snapshot name
+/// `paths`: config files
+pub fn snapshot_target<'a>(
+    dir: &Path,
+    name: &str,
+    paths: &[&'a Path],
+) -> Result<(PathBuf, &'a Path)> {
+    let snapshot = list_snapshots(dir, paths)?
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or(anyhow!("snapshot {} not found", name))?;
+    let target = paths
+        .iter()
+        .find(|p| file_name(p).map(|f| f == snapshot.file).unwrap_or(false))
+        .ok_or(anyhow!("config file of snapshot {} not found", name))?;
+    Ok((dir.join(&snapshot.name), target))
+}
+
+/// Restore snapshot to config file,
+/// current config file will be snapshotted first so restore can be undone.
+///
+/// ## Arguments
+///
+/// `dir`: snapshot folder
+/// `snapshot`: snapshot file path
+/// `target`: config file path
+pub fn restore_snapshot(dir: &Path, snapshot: &Path, target: &Path) -> Result<()> {
+    let content = fs::read(snapshot)?;
+    if target.exists() && fs::read(target)? != content {
+        force_snapshot(dir, target)?;
+    }
+    atomic_write(target, &content)?;
+    info!(
+        "Restored {} from snapshot {}",
+        target.display(),
+        snapshot.display()
+    );
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    Ok(path
+        .file_name()
+        .ok_or(anyhow!("file name of {} is empty", path.display()))?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Line diff from `old` to `new`
+pub fn diff_lines(old: &str, new: &str) -> Result<Vec<DiffLine>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let line = |tag: DiffTag, content: &str| DiffLine {
+        tag,
+        content: content.to_owned(),
+    };
+    let mut result = old[..prefix]
+        .iter()
+        .map(|l| line(DiffTag::Equal, l))
+        .collect::<Vec<_>>();
+
+    let (n, m) = (old_mid.len(), new_mid.len());
+    if n * m > 4_000_000 {
+        bail!("files are too different to diff");
+    }
+    // Longest common subsequence of the changed part
+    let mut lcs = vec![vec![0_u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            result.push(line(DiffTag::Equal, old_mid[i]));
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            result.push(line(DiffTag::Insert, new_mid[j]));
+            j += 1;
+        } else {
+            result.push(line(DiffTag::Delete, old_mid[i]));
+            i += 1;
+        }
+    }
+
+    result.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|l| line(DiffTag::Equal, l)),
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// Empty folder in system temp folder
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("venus-snapshot-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn force_snapshot_names_are_unique() {
+        let dir = temp_dir("unique");
+        let path = dir.join("config.toml");
+        fs::write(&path, "version = \"0.1.3\"").unwrap();
+        let snapshots = dir.join("snapshots");
+        let names = (0..SNAPSHOT_LIMIT + 5)
+            .map(|_| force_snapshot(&snapshots, &path).unwrap().unwrap())
+            .collect::<Vec<_>>();
+
+        let listed = list_snapshots(&snapshots, &[&path]).unwrap();
+        assert_eq!(listed.len(), SNAPSHOT_LIMIT);
+        // newest first, oldest are pruned
+        let newest = names.iter().rev().take(SNAPSHOT_LIMIT);
+        assert!(listed.iter().map(|s| &s.name).eq(newest));
+    }
+
+    #[test]
+    fn list_legacy_snapshots() {
+        let dir = temp_dir("legacy");
+        fs::write(dir.join("config.toml.20240306-120000"), "").unwrap();
+        let path = dir.join("config.toml");
+        let listed = list_snapshots(&dir, &[&path]).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].created_at, "2024-03-06 12:00:00");
+    }
+}
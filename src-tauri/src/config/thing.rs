@@ -1,7 +1,15 @@
-use crate::utils::consts::SPEED_URL;
-use crate::{commands::subs::NodeType, utils::consts::VERSION};
+use super::paths::AppPaths;
+use crate::utils::consts::{GEOIP_URL, GEOSITE_URL, PROBE_URL, SPEED_URL};
+use crate::{
+    commands::subs::NodeType,
+    utils::{
+        consts::VERSION,
+        lenient::{lenient_bool, string_or_number, without_null},
+    },
+};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, path::PathBuf};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -37,6 +45,8 @@ pub struct RUABasicSetting {
     // TODO speed timeout
     pub update_subs: Option<SubsAutoUpdate>,
     pub update_time: Option<u16>,
+    #[serde(default)]
+    pub log: LogSetting,
 }
 impl Default for RUABasicSetting {
     fn default() -> Self {
@@ -44,6 +54,31 @@ impl Default for RUABasicSetting {
             speed_url: SPEED_URL.into(),
             update_subs: Some(SubsAutoUpdate::Off),
             update_time: None,
+            log: LogSetting::default(),
+        }
+    }
+}
+
+/// Log level and log files rotation
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSetting {
+    /// "error" | "warn" | "info" | "debug" | "trace"
+    pub level: String,
+    /// Max size of single log file in MB, 0 means no limit
+    pub max_size: u64,
+    /// Max count of rotated log files
+    pub max_files: usize,
+    /// Rotated log files older than this days will be removed, 0 means keep forever
+    pub max_days: u64,
+}
+impl Default for LogSetting {
+    fn default() -> Self {
+        Self {
+            level: "info".into(),
+            max_size: 10,
+            max_files: 5,
+            max_days: 7,
         }
     }
 }
@@ -61,6 +96,33 @@ pub struct RConfig {
     /// Subscriptions
     pub subscriptions: Vec<Subscription>,
     pub settings: RUABasicSetting,
+    /// Geo data files update urls and installed versions
+    #[serde(default)]
+    pub geo: GeoSetting,
+    /// Active profile name, empty if no profile is activated
+    #[serde(default)]
+    pub profile: String,
+    /// Share inbounds to other devices in LAN
+    #[serde(default)]
+    pub lan: LanSetting,
+    /// Routing mode, decides the rules passed to core
+    #[serde(default)]
+    pub mode: ProxyMode,
+    /// Remote domain and IP lists compiled into routing rules
+    #[serde(default)]
+    pub rule_sets: Vec<RuleSet>,
+    /// Load balancing group used instead of the selected node
+    #[serde(default)]
+    pub balancer: BalancerGroup,
+    /// Multi-hop node chains, selected like nodes
+    #[serde(default)]
+    pub chains: Vec<ProxyChain>,
+    /// Outbound settings of nodes, kept when subscriptions are updated
+    #[serde(default)]
+    pub overrides: NodeOverrides,
+    /// Network interface or source address used by outbounds
+    #[serde(default)]
+    pub bind: OutboundBind,
 }
 impl Default for RConfig {
     fn default() -> Self {
@@ -71,46 +133,331 @@ impl Default for RConfig {
             save_windows: true,
             subscriptions: vec![],
             settings: RUABasicSetting::default(),
+            geo: GeoSetting::default(),
+            profile: String::new(),
+            lan: LanSetting::default(),
+            mode: ProxyMode::default(),
+            rule_sets: vec![],
+            balancer: BalancerGroup::default(),
+            chains: vec![],
+            overrides: NodeOverrides::default(),
+            bind: OutboundBind::default(),
+        }
+    }
+}
+
+/// Routing mode
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ProxyMode {
+    /// Everything goes to `proxy` except private IPs
+    Global,
+    /// Use routing rules in core config
+    Rule,
+    /// Everything goes to `direct`
+    Direct,
+}
+impl Default for ProxyMode {
+    fn default() -> Self {
+        Self::Rule
+    }
+}
+impl ProxyMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProxyMode::Global => "Global",
+            ProxyMode::Rule => "Rule",
+            ProxyMode::Direct => "Direct",
+        }
+    }
+}
+
+/// Pin outgoing connections of `direct` and `proxy` outbounds,
+/// e.g. connect nodes over physical link while VPN is up
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboundBind {
+    #[serde(default)]
+    pub direct: BindTarget,
+    /// Used by node, chain and balancer outbounds
+    #[serde(default)]
+    pub proxy: BindTarget,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BindTarget {
+    /// Network interface name like "wlan0"
+    pub interface: Option<String>,
+    /// Source IP address, `sendThrough` of outbound
+    pub address: Option<String>,
+}
+
+/// Outbound overrides of nodes, node override fields take precedence
+/// over the default of its subscription
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeOverrides {
+    /// Node id to override
+    #[serde(default)]
+    pub nodes: BTreeMap<String, OutboundOverride>,
+    /// Subscription name to default override of its nodes
+    #[serde(default)]
+    pub subscriptions: BTreeMap<String, OutboundOverride>,
+}
+
+/// Settings applied to the outbound generated from node,
+/// None means not overridden
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutboundOverride {
+    pub mux: Option<bool>,
+    pub mux_concurrency: Option<u32>,
+    /// "AsIs" | "UseIP" | "UseIPv4" | "UseIPv6"
+    pub domain_strategy: Option<String>,
+    /// SO_MARK on Linux
+    pub mark: Option<i64>,
+    pub tcp_fast_open: Option<bool>,
+    /// Network interface used to connect node
+    pub interface: Option<String>,
+    /// JSON merge patch applied to generated outbound
+    pub patch: Option<String>,
+}
+
+/// Nodes connected one by one, traffic goes through
+/// the first node to the last node then to destination.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyChain {
+    pub name: String,
+    /// Node ids, from the first hop to the exit node
+    pub nodes: Vec<String>,
+    // Speed test results, same as node
+    pub speed: Option<f64>,
+    pub delay: Option<u64>,
+    pub connectivity: Option<bool>,
+}
+
+/// Nodes used as a load balancing group, each node becomes an outbound
+/// and traffic to `proxy` goes to the balancer instead.
+///
+/// Missing fields use values of `BalancerGroup::default()`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct BalancerGroup {
+    pub enabled: bool,
+    /// Selected node ids
+    pub nodes: Vec<String>,
+    /// Use all nodes of this subscription
+    pub subscription: Option<String>,
+    /// Only use subscription nodes whose name contains it
+    pub filter: Option<String>,
+    pub strategy: BalanceStrategy,
+    /// Observatory probe url, used by leastPing
+    pub probe_url: String,
+    /// Observatory probe interval like "1m"
+    pub probe_interval: String,
+}
+impl Default for BalancerGroup {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            nodes: vec![],
+            subscription: None,
+            filter: None,
+            strategy: BalanceStrategy::default(),
+            probe_url: PROBE_URL.into(),
+            probe_interval: "1m".into(),
+        }
+    }
+}
+
+/// How balancer picks outbound
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum BalanceStrategy {
+    Random,
+    /// Lowest delay measured by observatory
+    LeastPing,
+}
+impl Default for BalanceStrategy {
+    fn default() -> Self {
+        Self::Random
+    }
+}
+impl BalanceStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BalanceStrategy::Random => "random",
+            BalanceStrategy::LeastPing => "leastPing",
+        }
+    }
+}
+
+/// Remote rule list, entries are stored in `rules/{name}.json`
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleSet {
+    pub name: String,
+    pub url: String,
+    pub format: RuleSetFormat,
+    /// Matched traffic goes to this outbound
+    pub outbound_tag: String,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub domain_count: usize,
+    #[serde(default)]
+    pub ip_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// Error of last update
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum RuleSetFormat {
+    /// One domain, IP or CIDR per line
+    Domains,
+    /// Hosts file like ad-block lists, `0.0.0.0 ads.example.com`
+    Hosts,
+    /// AutoProxy format in base64 like GFWList
+    AutoProxy,
+}
+impl Default for RuleSetFormat {
+    fn default() -> Self {
+        Self::Domains
+    }
+}
+
+/// LAN sharing setting
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LanSetting {
+    pub enabled: bool,
+    /// Listen address when sharing, "0.0.0.0" or a interface address
+    pub address: String,
+    /// Tags of shared inbounds
+    pub inbounds: Vec<String>,
+    /// Inbound settings before sharing, restored when sharing is turned off
+    pub saved: Vec<LanSaved>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LanSaved {
+    pub tag: String,
+    pub listen: String,
+    pub allow_transparent: bool,
+}
+
+/// Geo data (geoip.dat and geosite.dat) update setting
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoSetting {
+    pub geoip_url: String,
+    pub geosite_url: String,
+    /// Installed geo data files
+    pub files: Vec<GeoFile>,
+}
+impl Default for GeoSetting {
+    fn default() -> Self {
+        Self {
+            geoip_url: GEOIP_URL.into(),
+            geosite_url: GEOSITE_URL.into(),
+            files: vec![],
         }
     }
 }
 
+/// Installed geo data file, `previous` is the version kept for rollback
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoFile {
+    /// File name, `geoip.dat` or `geosite.dat`
+    pub name: String,
+    pub current: Option<GeoVersion>,
+    pub previous: Option<GeoVersion>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoVersion {
+    /// Last-Modified header of the download response
+    pub version: String,
+    pub sha256: String,
+    pub updated_at: String,
+}
+
 /// All config field
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VConfig {
+    /// Folders of config, data and log files
+    pub paths: AppPaths,
     pub core: Option<CoreConfig>,
     pub core_path: PathBuf,
     pub rua: RConfig,
     pub rua_path: PathBuf,
+    /// User overlay file, merged into core config
+    pub overlay_path: PathBuf,
+    /// The config file which core actually runs with
+    pub runtime_path: PathBuf,
 }
 
 /// Subscription nodes
+///
+/// Share link fields accept numbers and may be missing,
+/// unknown fields are kept in `extra` so the link can be exported unchanged.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Node {
+    #[serde(default, deserialize_with = "string_or_number")]
     pub v: String,
     // Node name
+    #[serde(default, deserialize_with = "string_or_number")]
     pub ps: String,
     // Address
+    #[serde(default, deserialize_with = "string_or_number")]
     pub add: String,
+    #[serde(default, deserialize_with = "string_or_number")]
     pub port: String,
+    #[serde(default, deserialize_with = "string_or_number")]
     pub id: String,
     // AlertID
+    #[serde(default, deserialize_with = "string_or_number")]
     pub aid: String,
+    // User security like "auto" or "aes-128-gcm"
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub scy: String,
     // Protocol type determine streamSettings network field
+    #[serde(default, deserialize_with = "string_or_number")]
     pub net: String,
     // Protocol type
     #[serde(rename = "type")]
+    #[serde(default, deserialize_with = "string_or_number")]
     pub type_field: String,
+    #[serde(default, deserialize_with = "string_or_number")]
     pub host: String,
     // streamSettings
+    #[serde(default, deserialize_with = "string_or_number")]
     pub path: String,
     // Determine streamSettings security field
+    #[serde(default, deserialize_with = "string_or_number")]
     pub tls: String,
     // Determine streamSettings headers sni
+    #[serde(default, deserialize_with = "string_or_number")]
     pub sni: String,
+    #[serde(default, deserialize_with = "string_or_number")]
     pub alpn: String,
+    // uTLS fingerprint like "chrome"
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub fp: String,
+    // Skip TLS certificate verification, some providers use `allowInsecure`
+    // which is kept in `extra` to export the link unchanged
+    #[serde(default, deserialize_with = "lenient_bool")]
+    pub insecure: Option<bool>,
     // Add by manually
     // The subscription group
     pub subs: Option<String>,
@@ -126,84 +473,200 @@ pub struct Node {
     pub raw_link: Option<String>,
     // Node net type
     pub node_type: Option<NodeType>,
+    // Unknown share link fields, null is dropped because toml can't store it
+    #[serde(flatten, deserialize_with = "without_null")]
+    pub extra: Map<String, Value>,
+}
+
+impl Node {
+    /// Whether to skip TLS certificate verification,
+    /// `insecure` takes precedence over `allowInsecure`
+    pub fn allow_insecure(&self) -> bool {
+        self.insecure
+            .or_else(|| {
+                self.extra
+                    .get("allowInsecure")
+                    .and_then(|value| lenient_bool(value.clone()).ok().flatten())
+            })
+            .unwrap_or(false)
+    }
 }
 
 /// Core config root
+///
+/// Every struct keeps the keys it doesn't know in `extra`,
+/// so reload and write core config never loses anything.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CoreConfig {
+    #[serde(default)]
     pub log: Log,
+    #[serde(default)]
     pub inbounds: Vec<Inbound>,
+    #[serde(default)]
     pub outbounds: Vec<Outbound>,
+    #[serde(default)]
     pub routing: Routing,
+    #[serde(default)]
     pub dns: Dns,
-    pub policy: Policy,
-    pub other: Other,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fakedns: Option<FakeDns>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<Policy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observatory: Option<Observatory>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Port number, or port range and env variable like "1000-2000", "env:PORT"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Port {
+    Number(u16),
+    Range(String),
+}
+impl Default for Port {
+    fn default() -> Self {
+        Port::Number(0)
+    }
+}
+impl std::fmt::Display for Port {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Port::Number(port) => write!(f, "{}", port),
+            Port::Range(range) => write!(f, "{}", range),
+        }
+    }
+}
+impl Port {
+    /// Single port number, or None if it's a range
+    pub fn number(&self) -> Option<u16> {
+        match self {
+            Port::Number(port) => Some(*port),
+            Port::Range(range) => range.trim().parse().ok(),
+        }
+    }
+}
+
+/// Skip writing `false` which is the default value of core
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Log {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub loglevel: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub access: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<PathBuf>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Inbound {
-    pub port: u16,
+    pub port: Port,
     // Listen address
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub listen: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub tag: String,
     pub protocol: String,
+    #[serde(default)]
     pub settings: InboundSettings,
     // Traffic sniffing
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sniffing: Option<Sniffing>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InboundSettings {
+    // "noauth" | "password", socks only
     #[serde(skip_serializing_if = "Option::is_none")]
     pub auth: Option<String>,
+    /// Users of socks and http inbound
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<InboundAccount>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
     pub udp: bool,
     // pub ip: String,
     // for dokodemo-door
     // pub address: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
     pub allow_transparent: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundAccount {
+    pub user: String,
+    pub pass: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Sniffing {
     pub enabled: bool,
+    #[serde(default)]
     pub dest_override: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub route_only: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Outbound {
     pub protocol: String,
+    #[serde(default)]
     pub settings: OutboundSettings,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub tag: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream_settings: Option<StreamSettings>,
+    #[serde(rename = "proxySettings")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxy_setting: Option<ProxySetting>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mux: Option<Mux>,
+    /// How to resolve domain of server address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_strategy: Option<String>,
+    /// Source IP address of outgoing connections
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_through: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StreamSettings {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub network: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub security: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tls_settings: Option<TlsSettings>,
@@ -220,156 +683,244 @@ pub struct StreamSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub quic_settings: Option<QuicSettings>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_settings: Option<GrpcSettings>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub sockopt: Option<Sockopt>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TlsSettings {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub server_name: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
     pub allow_insecure: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub alpn: Vec<String>,
-    pub certificates: Vec<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub certificates: Vec<Value>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
     pub disable_system_root: bool,
+    /// uTLS client fingerprint like "chrome", empty uses Go TLS
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TcpSettings {
-    pub header: KcpHeader,
+    #[serde(default)]
+    pub header: TcpHeader,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// TCP header obfuscation, request and response only used when type is "http"
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TcpHeader {
+    #[serde(rename = "type")]
+    #[serde(default)]
+    pub type_field: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request: Option<Request>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response: Option<Response>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Request {
-    pub version: String,
-    pub method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(default)]
     pub path: Vec<String>,
-    pub headers: Headers,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Headers {
-    #[serde(rename = "Host")]
-    pub host: Vec<String>,
-    #[serde(rename = "User-Agent")]
-    pub user_agent: Vec<String>,
-    #[serde(rename = "Accept-Encoding")]
-    pub accept_encoding: Vec<String>,
-    #[serde(rename = "Connection")]
-    pub connection: Vec<String>,
-    #[serde(rename = "Pragma")]
-    pub pragma: String,
+    /// Header name to a string or list of strings
+    #[serde(default)]
+    pub headers: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Response {
-    pub version: String,
-    pub status: String,
-    pub reason: String,
-    pub headers: Headers2,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Headers2 {
-    #[serde(rename = "Content-Type")]
-    pub content_type: Vec<String>,
-    #[serde(rename = "Transfer-Encoding")]
-    pub transfer_encoding: Vec<String>,
-    #[serde(rename = "Connection")]
-    pub connection: Vec<String>,
-    #[serde(rename = "Pragma")]
-    pub pragma: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Header name to a string or list of strings
+    #[serde(default)]
+    pub headers: Map<String, Value>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KcpSettings {
-    pub mtu: i64,
-    pub tti: i64,
-    pub uplink_capacity: i64,
-    pub downlink_capacity: i64,
-    pub congestion: bool,
-    pub read_buffer_size: i64,
-    pub write_buffer_size: i64,
-    pub header: KcpHeader,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tti: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uplink_capacity: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downlink_capacity: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub congestion: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_buffer_size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_buffer_size: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub header: Option<KcpHeader>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct KcpHeader {
     #[serde(rename = "type")]
+    #[serde(default)]
     pub type_field: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WsSettings {
+    #[serde(default)]
     pub path: String,
+    #[serde(default)]
     pub headers: WsHeaders,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WsHeaders {
     #[serde(rename = "Host")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub host: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct HttpSettings {
+    #[serde(default)]
     pub host: Vec<String>,
+    #[serde(default)]
     pub path: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DsSettings {
-    path: String,
+    #[serde(default)]
+    pub path: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QuicSettings {
+    #[serde(default)]
     pub security: String,
+    #[serde(default)]
     pub key: String,
+    #[serde(default)]
     pub header: KcpHeader,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrpcSettings {
+    #[serde(default)]
+    pub service_name: String,
+    /// Use multi mode instead of gun mode
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    pub multi_mode: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Sockopt {
-    pub mark: i64,
-    pub tcp_fast_open: bool,
-    pub tproxy: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mark: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_fast_open: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tproxy: Option<String>,
+    /// Network interface name, like "eth0"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bind_to_device: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ProxySetting {
     pub tag: String,
+    /// Dial through the proxy at transport layer, so stream settings still work
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transport_layer: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Mux {
     pub enabled: bool,
+    #[serde(default)]
     pub concurrency: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
+/// Outbound settings, only vmess `vnext` is modeled,
+/// settings of other protocols like freedom and blackhole are kept in `extra`
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OutboundSettings {
-    #[serde(default)]
-    pub vnext: Vec<Vmess>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vnext: Option<Vec<Vmess>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -377,16 +928,26 @@ pub struct OutboundSettings {
 pub struct Vmess {
     pub address: String,
     pub port: u16,
+    #[serde(default)]
     pub users: Vec<CoreUser>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CoreUser {
     pub id: String,
+    #[serde(default)]
     pub alter_id: u16,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub email: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub security: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // https://www.v2ray.com/chapter_02/03_routing.html
@@ -394,52 +955,61 @@ pub struct CoreUser {
 #[serde(rename_all = "camelCase")]
 pub struct Routing {
     // "AsIs" | "IPIfNonMatch" | "IPOnDemand"
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub domain_strategy: String,
+    #[serde(default)]
     pub rules: Vec<Rule>,
+    #[serde(default)]
     pub balancers: Vec<Balancers>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Rule {
     #[serde(rename = "type")]
+    #[serde(default)]
     pub type_field: String,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ip: Option<Vec<String>>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub domain: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub port: Option<String>,
+    pub port: Option<Port>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub source: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub inbound_tag: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub protocol: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub attrs: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
     pub outbound_tag: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub balancer_tag: Option<String>,
+    /// Disabled rules are kept in config but not passed to core
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_false")]
+    pub disabled: bool,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 impl Rule {
     pub fn new(outbound_tag: String) -> Self {
         Self {
             type_field: "field".into(),
-            ip: None,
-            domain: None,
-            port: None,
-            network: None,
-            source: None,
-            user: None,
-            inbound_tag: None,
-            protocol: None,
-            attrs: None,
             outbound_tag,
-            balancer_tag: None,
+            ..Default::default()
         }
     }
 }
@@ -448,67 +1018,271 @@ impl Rule {
 #[serde(rename_all = "camelCase")]
 pub struct Balancers {
     pub tag: String,
+    #[serde(default)]
     pub selector: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<BalancerStrategy>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Dns {
-    pub hosts: Hosts,
-    pub servers: (String, Servers, String, String),
+pub struct BalancerStrategy {
+    // "random" | "leastPing"
+    #[serde(rename = "type")]
+    pub type_field: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
+/// Probe outbounds for balancer, required by leastPing strategy
+// https://www.v2fly.org/config/observatory.html
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Hosts {
-    #[serde(rename = "domain:v2fly.org")]
-    pub domain_v2fly_org: String,
-    #[serde(rename = "domain:github.io")]
-    pub domain_github_io: String,
-    #[serde(rename = "domain:wikipedia.org")]
-    pub domain_wikipedia_org: String,
-    #[serde(rename = "domain:shadowsocks.org")]
-    pub domain_shadowsocks_org: String,
+pub struct Observatory {
+    /// Outbound tag prefixes to be probed
+    #[serde(default)]
+    pub subject_selector: Vec<String>,
+    #[serde(rename = "probeURL")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub probe_url: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub probe_interval: String,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+// https://www.v2fly.org/config/dns.html
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dns {
+    /// Domain matcher to a address or list of addresses
+    #[serde(default)]
+    pub hosts: BTreeMap<String, HostAddress>,
+    #[serde(default)]
+    pub servers: Vec<DnsServer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    // "UseIP" | "UseIPv4" | "UseIPv6"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_strategy: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_cache: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_fallback: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Address of hosts, single or multiple
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HostAddress {
+    Single(String),
+    Multiple(Vec<String>),
+}
+impl HostAddress {
+    pub fn addresses(&self) -> Vec<&str> {
+        match self {
+            HostAddress::Single(address) => vec![address.as_str()],
+            HostAddress::Multiple(addresses) => addresses.iter().map(|a| a.as_str()).collect(),
+        }
+    }
+}
+
+/// DNS server, a plain address string or a object with options.
+/// Address can be IP, "localhost", "fakedns",
+/// or DoH/DoT/DoQ url like "https://1.1.1.1/dns-query", "tcp+local://223.5.5.5"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DnsServer {
+    Address(String),
+    Server(DnsServerConfig),
+}
+impl DnsServer {
+    pub fn address(&self) -> &str {
+        match self {
+            DnsServer::Address(address) => address,
+            DnsServer::Server(server) => &server.address,
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Servers {
+pub struct DnsServerConfig {
     pub address: String,
-    pub port: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Domain matchers which prefer this server
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub domains: Vec<String>,
+    /// IP matchers, results not in the list will be dropped
+    #[serde(rename = "expectIPs")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub expect_ips: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_fallback: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_strategy: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Fake DNS pool, single pool or list of pools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FakeDns {
+    Pool(FakeDnsPool),
+    Pools(Vec<FakeDnsPool>),
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Policy {
-    pub levels: Levels,
-    pub system: System,
+pub struct FakeDnsPool {
+    pub ip_pool: String,
+    pub pool_size: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Levels {
-    #[serde(rename = "0")]
-    pub n0: N0,
+pub struct Policy {
+    /// User level to policy
+    #[serde(default)]
+    pub levels: BTreeMap<String, PolicyLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<System>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct N0 {
-    pub uplink_only: i64,
-    pub downlink_only: i64,
+pub struct PolicyLevel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uplink_only: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downlink_only: Option<i64>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct System {
-    pub stats_inbound_uplink: bool,
-    pub stats_inbound_downlink: bool,
-    pub stats_outbound_uplink: bool,
-    pub stats_outbound_downlink: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_inbound_uplink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_inbound_downlink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_outbound_uplink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_outbound_downlink: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Other {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keep_unknown_core_fields() {
+        let config = json!({
+            "log": { "loglevel": "warning", "dnsLog": true },
+            "inbounds": [{
+                "tag": "socks",
+                "port": 10808,
+                "listen": "127.0.0.1",
+                "protocol": "socks",
+                "settings": { "auth": "noauth", "udp": true, "ip": "127.0.0.1" },
+                "sniffing": { "enabled": true, "destOverride": ["http", "tls"] }
+            }],
+            "outbounds": [{
+                "tag": "direct",
+                "protocol": "freedom",
+                "settings": { "domainStrategy": "UseIP" },
+                "streamSettings": {
+                    "network": "tcp",
+                    "sockopt": { "mark": 255, "tcpKeepAliveInterval": 30 }
+                },
+                "proxySettings": { "tag": "proxy" }
+            }],
+            "routing": {
+                "domainStrategy": "IPIfNonMatch",
+                "rules": [{ "type": "field", "outboundTag": "direct", "ip": ["geoip:private"] }],
+                "balancers": []
+            },
+            "dns": {
+                "hosts": { "dns.google": "8.8.8.8" },
+                "servers": ["1.1.1.1"],
+                "queryStrategy": "UseIPv4"
+            },
+            "stats": {},
+            "reverse": { "bridges": [{ "tag": "bridge", "domain": "example.com" }] }
+        });
+        let core: CoreConfig = serde_json::from_value(config.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&core).unwrap(), config);
+    }
+
+    #[test]
+    fn decode_share_link_leniently() {
+        let node: Node = serde_json::from_str(
+            r#"{"v":2,"ps":"hk","add":"1.2.3.4","port":443,"id":"uuid","aid":0,
+            "tls":"tls","insecure":"1","host":null}"#,
+        )
+        .unwrap();
+        assert_eq!(node.v, "2");
+        assert_eq!(node.port, "443");
+        assert_eq!(node.aid, "0");
+        assert_eq!(node.host, "");
+        assert_eq!(node.insecure, Some(true));
+        assert!(node.allow_insecure());
+
+        // missing net is tcp
+        let stream = crate::config::stream_settings_builder(&node).unwrap();
+        assert_eq!(stream.network, "tcp");
+
+        assert!(serde_json::from_str::<Node>(r#"{"ps":"jp","insecure":"yes"}"#).is_err());
+    }
+
+    #[test]
+    fn keep_allow_insecure_as_received() {
+        let node: Node =
+            serde_json::from_str(r#"{"ps":"hk","allowInsecure":1,"net":"ws"}"#).unwrap();
+        assert_eq!(node.insecure, None);
+        assert!(node.allow_insecure());
+
+        // nodes are stored and exported as toml
+        let stored = toml::Value::try_from(&node).unwrap();
+        assert_eq!(stored.get("allowInsecure"), Some(&toml::Value::Integer(1)));
+        assert!(stored.get("insecure").is_none());
+    }
+
+    #[test]
+    fn drop_null_extra_fields() {
+        let node: Node = serde_json::from_str(
+            r#"{"ps":"hk","flow":null,"reality":{"pbk":"key","sid":null},"ports":[1,null]}"#,
+        )
+        .unwrap();
+        assert!(!node.extra.contains_key("flow"));
+        assert_eq!(node.extra["reality"], json!({ "pbk": "key" }));
+        assert_eq!(node.extra["ports"], json!([1]));
+        // toml has no null
+        let stored = toml::to_string(&node).unwrap();
+        let restored: Node = toml::from_str(&stored).unwrap();
+        assert_eq!(restored, node);
+    }
+}
@@ -0,0 +1,242 @@
+use super::{
+    chain::{outbounds_by_id, replace_outbounds},
+    SubsAutoUpdate, VConfig,
+};
+use crate::{
+    commands::subs::timer_update,
+    logger::apply_log_setting,
+    message::{ConfigMsg, MSG_TX},
+    CONFIG, LOGGING, UPDATE_TIMER,
+};
+use anyhow::{anyhow, bail, Result};
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, time::sleep};
+
+/// How often config files are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Reload after file stopped changing for this long,
+/// editors may write a file multiple times when saving
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Hash of config file content last read or written by Venus,
+/// used to ignore Venus's own writes.
+static KNOWN: Lazy<Mutex<HashMap<PathBuf, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn hash_content(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Record config file content which is read or written by Venus,
+/// the file is valid again if it was invalid
+pub fn mark_known(path: &Path, content: &[u8]) {
+    if let Ok(mut known) = KNOWN.lock() {
+        known.insert(path.to_owned(), hash_content(content));
+    }
+    if let Ok(mut invalid) = INVALID.lock() {
+        invalid.remove(path);
+    }
+}
+
+/// Config files edited by other programs which can't be parsed,
+/// Venus doesn't overwrite them until they are fixed.
+static INVALID: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Refuse to overwrite config file which is being edited by other programs
+pub fn check_writable(path: &Path) -> Result<()> {
+    let invalid = INVALID
+        .lock()
+        .map(|invalid| invalid.contains(path))
+        .unwrap_or(false);
+    if invalid {
+        bail!(
+            "config file {} is invalid, fix it before changing settings",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn mark_invalid(path: &Path) {
+    if let Ok(mut invalid) = INVALID.lock() {
+        invalid.insert(path.to_owned());
+    }
+}
+
+fn is_known(path: &Path, content: &[u8]) -> bool {
+    KNOWN
+        .lock()
+        .map(|known| known.get(path) == Some(&hash_content(content)))
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WatchedFile {
+    Core,
+    Rua,
+}
+
+/// Modified time and size of file
+async fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = fs::metadata(path).await.ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+/// Watch `config.json` and `config.toml`, reload config when they are edited
+/// by other programs. Never returns.
+///
+/// ## Arguments
+///
+/// `core_path`: `config.json` path
+/// `rua_path`: `config.toml` path
+pub async fn watch_config(core_path: PathBuf, rua_path: PathBuf) {
+    let mut files = vec![];
+    for (kind, path) in [(WatchedFile::Core, core_path), (WatchedFile::Rua, rua_path)] {
+        let stamp = file_stamp(&path).await;
+        files.push((kind, path, stamp, None));
+    }
+    info!("Start watching config files");
+    loop {
+        sleep(POLL_INTERVAL).await;
+        for (kind, path, last_stamp, changed_at) in files.iter_mut() {
+            let stamp = file_stamp(path).await;
+            if stamp != *last_stamp {
+                *last_stamp = stamp;
+                *changed_at = Some(SystemTime::now());
+                continue;
+            }
+            let settled = changed_at
+                .and_then(|t| t.elapsed().ok())
+                .map(|elapsed| elapsed >= DEBOUNCE)
+                .unwrap_or(false);
+            if !settled {
+                continue;
+            }
+            *changed_at = None;
+            if let Err(err) = reload_changed(*kind, path).await {
+                error!("Reload {} failed {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Reload changed config file, the file will not be touched if it's invalid
+async fn reload_changed(kind: WatchedFile, path: &Path) -> Result<()> {
+    let content = match fs::read(path).await {
+        Ok(content) => content,
+        // file may be removed and recreated by editor
+        Err(_) => return Ok(()),
+    };
+    if is_known(path, &content) {
+        return Ok(());
+    }
+    let mut config = CONFIG.lock().await;
+    // Venus may write it while waiting for lock
+    let content = fs::read(path).await?;
+    if is_known(path, &content) {
+        return Ok(());
+    }
+    info!("{} changed by other program, reloading", path.display());
+
+    // used to find out whether core need to be restarted
+    let current_id = config.rua.current_id.clone();
+    let runtime = config.runtime_core().ok();
+    let result = match kind {
+        WatchedFile::Core => config.reload_core(),
+        WatchedFile::Rua => config.reload_rua(),
+    };
+    if let Err(err) = result {
+        mark_invalid(path);
+        let err = format!(
+            "Config file {} is invalid {}, settings will not be saved until it's fixed",
+            path.display(),
+            err
+        );
+        error!("{err}");
+        drop(config);
+        MSG_TX
+            .lock()
+            .await
+            .send(ConfigMsg::ConfigError(err))
+            .await?;
+        return Ok(());
+    }
+
+    match kind {
+        WatchedFile::Core => {
+            config.write_runtime()?;
+            drop(config);
+            MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+        }
+        WatchedFile::Rua => {
+            apply_rua(&config)?;
+            let restart = update_runtime(&mut config, &current_id, runtime)?;
+            let settings = config.rua.settings.clone();
+            drop(config);
+            // timer job locks config, reschedule it after lock released
+            match settings.update_subs {
+                Some(SubsAutoUpdate::Time) => timer_update(settings.update_time).await,
+                Some(SubsAutoUpdate::Startup) => {}
+                _ => UPDATE_TIMER.lock().await.terminate(),
+            }
+            MSG_TX.lock().await.send(ConfigMsg::EmitConfig).await?;
+            if restart {
+                MSG_TX.lock().await.send(ConfigMsg::RestartCore).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Regenerate runtime config when reloaded rua config changed fields used by core,
+/// like selected node, routing mode, rule sets, balancer, bind and overrides.
+///
+/// ## Arguments
+///
+/// `current_id`: selected node before reload
+/// `runtime`: runtime config before reload
+///
+/// ## Return
+///
+/// Whether core need to be restarted
+fn update_runtime(config: &mut VConfig, current_id: &str, runtime: Option<Value>) -> Result<bool> {
+    let node_id = config.rua.current_id.clone();
+    if node_id != current_id && !node_id.is_empty() {
+        match outbounds_by_id(&node_id, &config.rua, "proxy") {
+            Ok(outbounds) => {
+                let core = config
+                    .core
+                    .as_mut()
+                    .ok_or(anyhow!("core config is empty"))?;
+                replace_outbounds(core, "proxy", outbounds);
+                config.write_core()?;
+                info!("Selected node changed to {}", node_id);
+            }
+            Err(err) => warn!("Node {} not found, keep current node {}", node_id, err),
+        }
+    }
+    let new_runtime = config.runtime_core()?;
+    if runtime.as_ref() == Some(&new_runtime) {
+        return Ok(false);
+    }
+    config.write_runtime()?;
+    Ok(true)
+}
+
+/// Apply logging settings in reloaded rua config,
+/// subscriptions are not updated even if update on startup is set
+fn apply_rua(config: &VConfig) -> Result<()> {
+    LOGGING.store(config.rua.logging, Ordering::Relaxed);
+    apply_log_setting(&config.log_dir(), &config.rua.settings.log)?;
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use crate::{
+    logger::CORE_TARGET,
     message::{ConfigMsg, MSG_TX},
     store::ui::CoreStatus,
     CORE, CORE_SHUTDOWN, UI,
@@ -68,10 +69,10 @@ fn start_core(path: &Path) -> Result<CommandChild> {
                     if line.contains("started") {
                         CORE_MSG_TX.send(CoreMessage::Started)?;
                     }
-                    info!("{line}");
+                    info!(target: CORE_TARGET, "{line}");
                 }
                 CommandEvent::Stderr(line) => {
-                    warn!("{line}");
+                    warn!(target: CORE_TARGET, "{line}");
                 }
                 CommandEvent::Terminated(line) => {
                     CORE_MSG_TX.send(CoreMessage::Stopping)?;
@@ -6,6 +6,7 @@ pub enum RUAEvents {
     UpdateUI,
     SpeedTest,
     EmitLog,
+    ConfigError,
 }
 
 impl RUAEvents {
@@ -17,6 +18,7 @@ impl RUAEvents {
             UpdateUI => "rua://update-ui",
             SpeedTest => "rua://speed-test",
             EmitLog => "rua://emit-log",
+            ConfigError => "rua://config-error",
         }
     }
 }
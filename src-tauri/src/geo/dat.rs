@@ -0,0 +1,581 @@
+//! Reader of v2ray `geosite.dat` and `geoip.dat`, they are protobuf
+//! `GeoSiteList` and `GeoIPList` messages.
+//! https://github.com/v2fly/v2ray-core/blob/master/app/router/routercommon/common.proto
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Protobuf field value
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// 32 and 64 bit values, not used by geo data
+    Fixed,
+}
+
+/// Minimal protobuf reader
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .buf
+                .get(self.pos)
+                .ok_or(anyhow!("unexpected end of geo data"))?;
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        bail!("invalid varint in geo data")
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.buf.len())
+            .ok_or(anyhow!("unexpected end of geo data"))?;
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Next field number and value, None at the end of message
+    fn field(&mut self) -> Result<Option<(u64, Field<'a>)>> {
+        if self.pos >= self.buf.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Field::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Fixed
+            }
+            wire => bail!("unsupported protobuf wire type {} in geo data", wire),
+        };
+        Ok(Some((key >> 3, value)))
+    }
+}
+
+fn to_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).to_string()
+}
+
+/// Raw entries of `GeoSiteList` or `GeoIPList`
+fn entries(data: &[u8]) -> Result<Vec<&[u8]>> {
+    let mut entries = vec![];
+    let mut list = Reader::new(data);
+    while let Some((number, field)) = list.field()? {
+        if let (1, Field::Bytes(entry)) = (number, field) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Code and entry count of a geosite category or geoip country
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoCode {
+    pub code: String,
+    pub count: usize,
+}
+
+/// List all codes in `GeoSiteList` or `GeoIPList` with entry count,
+/// entries are not decoded. Codes are lowercase as used in routing rules.
+pub fn list_codes(data: &[u8]) -> Result<Vec<GeoCode>> {
+    let mut codes = vec![];
+    for entry in entries(data)? {
+        let mut code = String::new();
+        let mut count = 0;
+        let mut reader = Reader::new(entry);
+        while let Some((number, field)) = reader.field()? {
+            match (number, field) {
+                (1, Field::Bytes(bytes)) => code = to_string(bytes).to_lowercase(),
+                (2, Field::Bytes(_)) => count += 1,
+                _ => {}
+            }
+        }
+        codes.push(GeoCode { code, count });
+    }
+    Ok(codes)
+}
+
+/// Find raw entry by code, code is case insensitive
+fn find_entry<'a>(data: &'a [u8], code: &str) -> Result<Option<&'a [u8]>> {
+    for entry in entries(data)? {
+        let mut reader = Reader::new(entry);
+        while let Some((number, field)) = reader.field()? {
+            if let (1, Field::Bytes(bytes)) = (number, field) {
+                if to_string(bytes).eq_ignore_ascii_case(code) {
+                    return Ok(Some(entry));
+                }
+                break;
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DomainType {
+    /// Keyword, matches if domain contains the value
+    Plain,
+    Regex,
+    /// Matches the domain and its subdomains
+    Domain,
+    Full,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteDomain {
+    pub kind: DomainType,
+    pub value: String,
+    /// Attribute keys like `cn` and `ads`
+    pub attributes: Vec<String>,
+    /// Compiled regex of `Regex` entry, compiled once when the entry is loaded
+    #[serde(skip)]
+    regex: Option<Regex>,
+}
+
+impl SiteDomain {
+    /// Test domain against this entry
+    pub fn matches(&self, domain: &str) -> bool {
+        match self.kind {
+            DomainType::Plain => domain.contains(&self.value),
+            DomainType::Regex => self
+                .regex
+                .as_ref()
+                .map(|r| r.is_match(domain))
+                .unwrap_or(false),
+            DomainType::Domain => {
+                domain == self.value
+                    || domain
+                        .strip_suffix(&self.value)
+                        .map(|prefix| prefix.ends_with('.'))
+                        .unwrap_or(false)
+            }
+            DomainType::Full => domain == self.value,
+        }
+    }
+
+    /// Matcher syntax used in routing rules
+    pub fn matcher(&self) -> String {
+        match self.kind {
+            DomainType::Plain => format!("keyword:{}", self.value),
+            DomainType::Regex => format!("regexp:{}", self.value),
+            DomainType::Domain => format!("domain:{}", self.value),
+            DomainType::Full => format!("full:{}", self.value),
+        }
+    }
+}
+
+/// A geosite category
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoSite {
+    pub code: String,
+    pub domains: Vec<SiteDomain>,
+}
+
+impl GeoSite {
+    /// All attribute keys used in this category, sorted
+    pub fn attributes(&self) -> Vec<String> {
+        let mut attributes = self
+            .domains
+            .iter()
+            .flat_map(|d| d.attributes.iter().cloned())
+            .collect::<Vec<_>>();
+        attributes.sort();
+        attributes.dedup();
+        attributes
+    }
+
+    /// Find first entry matches domain
+    ///
+    /// ## Arguments
+    ///
+    /// `domain`: the domain to be tested
+    /// `attribute`: only entries with this attribute, like `cn` in `geosite:google@cn`
+    pub fn find(&self, domain: &str, attribute: Option<&str>) -> Option<&SiteDomain> {
+        self.domains
+            .iter()
+            .filter(|d| {
+                attribute
+                    .map(|a| d.attributes.iter().any(|da| da == a))
+                    .unwrap_or(true)
+            })
+            .find(|d| d.matches(domain))
+    }
+}
+
+fn parse_domain(data: &[u8]) -> Result<SiteDomain> {
+    let mut domain = SiteDomain {
+        kind: DomainType::Plain,
+        value: String::new(),
+        attributes: vec![],
+        regex: None,
+    };
+    let mut reader = Reader::new(data);
+    while let Some((number, field)) = reader.field()? {
+        match (number, field) {
+            (1, Field::Varint(kind)) => {
+                domain.kind = match kind {
+                    1 => DomainType::Regex,
+                    2 => DomainType::Domain,
+                    3 => DomainType::Full,
+                    _ => DomainType::Plain,
+                }
+            }
+            (2, Field::Bytes(value)) => domain.value = to_string(value),
+            (3, Field::Bytes(attribute)) => {
+                let mut reader = Reader::new(attribute);
+                while let Some((number, field)) = reader.field()? {
+                    if let (1, Field::Bytes(key)) = (number, field) {
+                        domain.attributes.push(to_string(key));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if domain.kind == DomainType::Regex {
+        domain.regex = Regex::new(&domain.value).ok();
+    }
+    Ok(domain)
+}
+
+/// Load geosite category by code
+pub fn load_geosite(data: &[u8], code: &str) -> Result<Option<GeoSite>> {
+    find_entry(data, code)?.map(parse_geosite).transpose()
+}
+
+/// Load all geosite categories, used to search domain repeatedly
+pub fn load_geosites(data: &[u8]) -> Result<Vec<GeoSite>> {
+    entries(data)?.into_iter().map(parse_geosite).collect()
+}
+
+/// Find all geosite categories contain domain
+///
+/// ## Arguments
+///
+/// `sites`: categories loaded by `load_geosites`
+/// `domain`: the domain to be searched
+///
+/// ## Return
+///
+/// Category and the first entry matches domain in it
+pub fn search_geosite(sites: &[GeoSite], domain: &str) -> Vec<(String, SiteDomain)> {
+    sites
+        .iter()
+        .filter_map(|site| {
+            let found = site.find(domain, None)?;
+            Some((site.code.to_lowercase(), found.clone()))
+        })
+        .collect()
+}
+
+fn parse_geosite(entry: &[u8]) -> Result<GeoSite> {
+    let mut site = GeoSite {
+        code: String::new(),
+        domains: vec![],
+    };
+    let mut reader = Reader::new(entry);
+    while let Some((number, field)) = reader.field()? {
+        match (number, field) {
+            (1, Field::Bytes(code)) => site.code = to_string(code),
+            (2, Field::Bytes(domain)) => site.domains.push(parse_domain(domain)?),
+            _ => {}
+        }
+    }
+    Ok(site)
+}
+
+/// IP range of geoip country
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Cidr {
+    pub ip: IpAddr,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.ip, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let prefix = u32::from(self.prefix.min(32));
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let prefix = u32::from(self.prefix.min(128));
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.ip, self.prefix)
+    }
+}
+
+/// A geoip country
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoIp {
+    pub code: String,
+    pub cidrs: Vec<Cidr>,
+    /// Matches IPs which are not in cidrs
+    pub reverse_match: bool,
+}
+
+impl GeoIp {
+    /// Find the range contains ip, `reverse_match` is not applied
+    pub fn find(&self, ip: &IpAddr) -> Option<&Cidr> {
+        self.cidrs.iter().find(|cidr| cidr.contains(ip))
+    }
+}
+
+fn parse_cidr(data: &[u8]) -> Result<Cidr> {
+    let mut ip = None;
+    let mut prefix = 0;
+    let mut reader = Reader::new(data);
+    while let Some((number, field)) = reader.field()? {
+        match (number, field) {
+            (1, Field::Bytes(bytes)) => {
+                ip = match bytes.len() {
+                    4 => {
+                        let mut octets = [0_u8; 4];
+                        octets.copy_from_slice(bytes);
+                        Some(IpAddr::from(octets))
+                    }
+                    16 => {
+                        let mut octets = [0_u8; 16];
+                        octets.copy_from_slice(bytes);
+                        Some(IpAddr::from(octets))
+                    }
+                    len => bail!("invalid ip length {} in geoip", len),
+                }
+            }
+            (2, Field::Varint(value)) => prefix = value as u8,
+            _ => {}
+        }
+    }
+    Ok(Cidr {
+        ip: ip.ok_or(anyhow!("cidr without ip in geoip"))?,
+        prefix,
+    })
+}
+
+/// Load geoip country by code
+pub fn load_geoip(data: &[u8], code: &str) -> Result<Option<GeoIp>> {
+    find_entry(data, code)?.map(parse_geoip).transpose()
+}
+
+/// Find all geoip countries contain ip
+///
+/// ## Return
+///
+/// Country and the range contains ip, `reverse_match` is not applied
+pub fn search_geoip(data: &[u8], ip: &IpAddr) -> Result<Vec<(String, Cidr)>> {
+    let mut found = vec![];
+    for entry in entries(data)? {
+        let geoip = parse_geoip(entry)?;
+        if let Some(cidr) = geoip.find(ip) {
+            found.push((geoip.code.to_lowercase(), cidr.clone()));
+        }
+    }
+    Ok(found)
+}
+
+fn parse_geoip(entry: &[u8]) -> Result<GeoIp> {
+    let mut geoip = GeoIp {
+        code: String::new(),
+        cidrs: vec![],
+        reverse_match: false,
+    };
+    let mut reader = Reader::new(entry);
+    while let Some((number, field)) = reader.field()? {
+        match (number, field) {
+            (1, Field::Bytes(code)) => geoip.code = to_string(code),
+            (2, Field::Bytes(cidr)) => geoip.cidrs.push(parse_cidr(cidr)?),
+            (3, Field::Varint(reverse)) => geoip.reverse_match = reverse != 0,
+            _ => {}
+        }
+    }
+    Ok(geoip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut buf = vec![];
+        while value >= 0x80 {
+            buf.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        buf.push(value as u8);
+        buf
+    }
+
+    /// Length delimited field
+    fn bytes_field(number: u64, content: &[u8]) -> Vec<u8> {
+        let mut buf = varint(number << 3 | 2);
+        buf.extend(varint(content.len() as u64));
+        buf.extend(content);
+        buf
+    }
+
+    fn varint_field(number: u64, value: u64) -> Vec<u8> {
+        let mut buf = varint(number << 3);
+        buf.extend(varint(value));
+        buf
+    }
+
+    fn domain(kind: u64, value: &str, attributes: &[&str]) -> Vec<u8> {
+        let mut buf = varint_field(1, kind);
+        buf.extend(bytes_field(2, value.as_bytes()));
+        for attribute in attributes {
+            // Attribute { key = 1; bool_value = 2 }
+            let mut attr = bytes_field(1, attribute.as_bytes());
+            attr.extend(varint_field(2, 1));
+            buf.extend(bytes_field(3, &attr));
+        }
+        buf
+    }
+
+    fn geosite_list() -> Vec<u8> {
+        let mut google = bytes_field(1, b"GOOGLE");
+        google.extend(bytes_field(2, &domain(2, "google.com", &[])));
+        google.extend(bytes_field(2, &domain(3, "www.google.cn", &["cn"])));
+        google.extend(bytes_field(2, &domain(1, "^gstatic\\.", &[])));
+        let mut ads = bytes_field(1, b"CATEGORY-ADS");
+        ads.extend(bytes_field(2, &domain(0, "doubleclick", &["ads"])));
+
+        let mut list = bytes_field(1, &google);
+        list.extend(bytes_field(1, &ads));
+        list
+    }
+
+    fn cidr(ip: &[u8], prefix: u64) -> Vec<u8> {
+        let mut buf = bytes_field(1, ip);
+        buf.extend(varint_field(2, prefix));
+        buf
+    }
+
+    fn geoip_list() -> Vec<u8> {
+        let mut private = bytes_field(1, b"PRIVATE");
+        private.extend(bytes_field(2, &cidr(&[192, 168, 0, 0], 16)));
+        private.extend(bytes_field(
+            2,
+            &cidr(&[0xfc, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 7),
+        ));
+        let mut not_cn = bytes_field(1, b"CN");
+        not_cn.extend(bytes_field(2, &cidr(&[1, 0, 1, 0], 24)));
+        not_cn.extend(varint_field(3, 1));
+
+        let mut list = bytes_field(1, &private);
+        list.extend(bytes_field(1, &not_cn));
+        list
+    }
+
+    #[test]
+    fn list_geosite_codes() {
+        let codes = list_codes(&geosite_list()).unwrap();
+        let codes = codes
+            .iter()
+            .map(|c| (c.code.as_str(), c.count))
+            .collect::<Vec<_>>();
+        assert_eq!(codes, [("google", 3), ("category-ads", 1)]);
+    }
+
+    #[test]
+    fn load_geosite_by_code() {
+        let data = geosite_list();
+        let site = load_geosite(&data, "google").unwrap().unwrap();
+        assert_eq!(site.code, "GOOGLE");
+        assert_eq!(site.attributes(), ["cn"]);
+        assert_eq!(
+            site.find("mail.google.com", None).map(|d| d.matcher()),
+            Some("domain:google.com".into())
+        );
+        assert!(site.find("notgoogle.com", None).is_none());
+        assert!(site.find("google.com", Some("cn")).is_none());
+        assert_eq!(
+            site.find("www.google.cn", Some("cn")).map(|d| d.kind),
+            Some(DomainType::Full)
+        );
+        assert!(site.find("gstatic.com", None).is_some());
+        assert!(load_geosite(&data, "netflix").unwrap().is_none());
+    }
+
+    #[test]
+    fn search_geosite_by_domain() {
+        let sites = load_geosites(&geosite_list()).unwrap();
+        let found = search_geosite(&sites, "ad.doubleclick.net");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "category-ads");
+        assert_eq!(found[0].1.matcher(), "keyword:doubleclick");
+
+        let found = search_geosite(&sites, "gstatic.com");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1.matcher(), "regexp:^gstatic\\.");
+    }
+
+    #[test]
+    fn load_geoip_by_code() {
+        let data = geoip_list();
+        let private = load_geoip(&data, "private").unwrap().unwrap();
+        assert_eq!(private.cidrs.len(), 2);
+        assert!(private.find(&"192.168.1.1".parse().unwrap()).is_some());
+        assert!(private.find(&"fd00::1".parse().unwrap()).is_some());
+        assert!(private.find(&"8.8.8.8".parse().unwrap()).is_none());
+
+        let cn = load_geoip(&data, "CN").unwrap().unwrap();
+        assert!(cn.reverse_match);
+        assert_eq!(cn.cidrs[0].to_string(), "1.0.1.0/24");
+    }
+
+    #[test]
+    fn search_geoip_by_ip() {
+        let found = search_geoip(&geoip_list(), &"192.168.3.4".parse().unwrap()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "private");
+    }
+
+    #[test]
+    fn truncated_data() {
+        let data = geosite_list();
+        assert!(list_codes(&data[..data.len() - 3]).is_err());
+        assert!(entries(&[0x0a, 0xff]).is_err());
+    }
+}
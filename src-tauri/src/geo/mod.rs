@@ -0,0 +1,319 @@
+use crate::{
+    config::{GeoFile, GeoSetting, GeoVersion},
+    utils::consts::{NAME, VERSION},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::Local;
+use log::{info, warn};
+use reqwest::{
+    header::{LAST_MODIFIED, USER_AGENT},
+    Response,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tokio::{fs::File, io::AsyncWriteExt};
+
+pub mod dat;
+
+pub const GEOIP: &str = "geoip.dat";
+pub const GEOSITE: &str = "geosite.dat";
+
+/// Path of geo data file used by core, in `V2RAY_LOCATION_ASSET` folder
+pub fn asset_file(name: &str) -> PathBuf {
+    env::var_os("V2RAY_LOCATION_ASSET")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(name)
+}
+
+/// Copy bundled geo data files to writable asset folder
+/// if they are not exist or older than bundled ones,
+/// so data shipped with a new app version replaces old data.
+///
+/// ## Arguments
+///
+/// `resources`: bundled read only resources folder
+/// `assets`: writable asset folder
+///
+/// ## Return
+///
+/// Names of files which are replaced by bundled ones
+pub fn prepare_assets(resources: &Path, assets: &Path) -> Result<Vec<String>> {
+    fs::create_dir_all(assets)?;
+    let mut replaced = vec![];
+    for entry in fs::read_dir(resources)? {
+        let path = entry?.path();
+        if path.extension().map(|ext| ext != "dat").unwrap_or(true) {
+            continue;
+        }
+        let name = path.file_name().ok_or(anyhow!("file name is empty"))?;
+        let target = assets.join(name);
+        if !target.exists() {
+            fs::copy(&path, &target)?;
+        } else if is_newer(&path, &target) {
+            fs::copy(&path, &target)?;
+            let name = name.to_string_lossy().to_string();
+            info!("Geo data {} replaced by newer bundled file", name);
+            replaced.push(name);
+        }
+    }
+    Ok(replaced)
+}
+
+/// Whether `file` is modified later than `than`
+fn is_newer(file: &Path, than: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(file), modified(than)) {
+        (Some(file), Some(than)) => file > than,
+        _ => false,
+    }
+}
+
+/// Download url of geo data file
+pub fn geo_url<'a>(setting: &'a GeoSetting, name: &str) -> Result<&'a str> {
+    match name {
+        GEOIP => Ok(&setting.geoip_url),
+        GEOSITE => Ok(&setting.geosite_url),
+        _ => Err(anyhow!("unknown geo data file {}", name)),
+    }
+}
+
+/// Download geo data file and verify checksum with `{url}.sha256sum`.
+/// The previous file will be kept as `{name}.bak` for rollback.
+///
+/// ## Arguments
+///
+/// `assets`: writable asset folder
+/// `name`: `geoip.dat` or `geosite.dat`
+/// `url`: download url
+pub async fn download_geo(assets: &Path, name: &str, url: &str) -> Result<GeoVersion> {
+    info!("Start download geo data {} from {}", name, url);
+    let client = reqwest::ClientBuilder::new().no_proxy().build()?;
+    let user_agent = format!("{}/{}", NAME, VERSION);
+
+    let checksum = client
+        .get(format!("{}.sha256sum", url))
+        .header(USER_AGENT, &user_agent)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let checksum = checksum
+        .split_whitespace()
+        .next()
+        .ok_or(anyhow!("checksum of {} is empty", name))?
+        .to_lowercase();
+
+    let mut response = client
+        .get(url)
+        .header(USER_AGENT, &user_agent)
+        .send()
+        .await?
+        .error_for_status()?;
+    let version = response
+        .headers()
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
+
+    let download_path = assets.join(format!("{}.download", name));
+    let sha256 = match save_response(&mut response, &download_path).await {
+        Ok(sha256) => sha256,
+        Err(err) => {
+            let _ = fs::remove_file(&download_path);
+            return Err(err);
+        }
+    };
+    if sha256 != checksum {
+        let _ = fs::remove_file(&download_path);
+        bail!(
+            "checksum of {} mismatch, expected {} got {}",
+            name,
+            checksum,
+            sha256
+        );
+    }
+
+    let target = assets.join(name);
+    if target.exists() {
+        fs::rename(&target, backup_path(assets, name))?;
+    }
+    fs::rename(&download_path, &target)?;
+    info!("Geo data {} updated to {}", name, version);
+
+    Ok(GeoVersion {
+        version,
+        sha256,
+        updated_at: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    })
+}
+
+/// Write response body to file
+///
+/// ## Return
+///
+/// sha256 of the body
+async fn save_response(response: &mut Response, path: &Path) -> Result<String> {
+    let mut file = File::create(path).await?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = response.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.sync_all().await?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Swap geo data file with the `{name}.bak` file.
+pub fn rollback_geo(assets: &Path, name: &str) -> Result<()> {
+    if name != GEOIP && name != GEOSITE {
+        bail!("unknown geo data file {}", name);
+    }
+    let target = assets.join(name);
+    let backup = backup_path(assets, name);
+    if !backup.exists() {
+        bail!("no previous version of {}", name);
+    }
+    let swap = assets.join(format!("{}.swap", name));
+    if target.exists() {
+        fs::rename(&target, &swap)?;
+    }
+    fs::rename(&backup, &target)?;
+    if swap.exists() {
+        fs::rename(&swap, &backup)?;
+    }
+    warn!("Geo data {} rolled back", name);
+    Ok(())
+}
+
+fn backup_path(assets: &Path, name: &str) -> PathBuf {
+    assets.join(format!("{}.bak", name))
+}
+
+/// Record new installed version in setting
+pub fn record_version(setting: &mut GeoSetting, name: &str, version: GeoVersion) {
+    match setting.files.iter_mut().find(|f| f.name == name) {
+        Some(file) => {
+            file.previous = file.current.take();
+            file.current = Some(version);
+        }
+        None => setting.files.push(GeoFile {
+            name: name.into(),
+            current: Some(version),
+            previous: None,
+        }),
+    }
+}
+
+/// Swap current and previous version in setting
+pub fn record_rollback(setting: &mut GeoSetting, name: &str) {
+    if let Some(file) = setting.files.iter_mut().find(|f| f.name == name) {
+        std::mem::swap(&mut file.current, &mut file.previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::{io::AsyncReadExt, net::TcpListener};
+
+    /// Empty folder in system temp folder
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("venus-geo-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Local http server, `{url}.sha256sum` returns checksum, other paths return body
+    async fn serve(body: &'static [u8], checksum: String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![0; 4096];
+                let len = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..len]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let content = if path.ends_with(".sha256sum") {
+                    format!("{}  geoip.dat\n", checksum).into_bytes()
+                } else {
+                    body.to_vec()
+                };
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content.len()
+                );
+                let _ = stream.write_all(header.as_bytes()).await;
+                let _ = stream.write_all(&content).await;
+            }
+        });
+        format!("http://{}/geoip.dat", addr)
+    }
+
+    fn sha256(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    #[tokio::test]
+    async fn download_replaces_file_and_keeps_backup() {
+        let body = b"new geo data";
+        let url = serve(body, sha256(body)).await;
+        let dir = temp_dir("download");
+        fs::write(dir.join(GEOIP), b"old geo data").unwrap();
+
+        let version = download_geo(&dir, GEOIP, &url).await.unwrap();
+        assert_eq!(version.sha256, sha256(body));
+        assert_eq!(fs::read(dir.join(GEOIP)).unwrap(), body);
+        assert_eq!(fs::read(backup_path(&dir, GEOIP)).unwrap(), b"old geo data");
+
+        rollback_geo(&dir, GEOIP).unwrap();
+        assert_eq!(fs::read(dir.join(GEOIP)).unwrap(), b"old geo data");
+        assert_eq!(fs::read(backup_path(&dir, GEOIP)).unwrap(), body);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn download_rejects_checksum_mismatch() {
+        let url = serve(b"broken geo data", sha256(b"other")).await;
+        let dir = temp_dir("mismatch");
+        fs::write(dir.join(GEOIP), b"old geo data").unwrap();
+
+        assert!(download_geo(&dir, GEOIP, &url).await.is_err());
+        assert_eq!(fs::read(dir.join(GEOIP)).unwrap(), b"old geo data");
+        assert!(!dir.join(format!("{}.download", GEOIP)).exists());
+        assert!(!backup_path(&dir, GEOIP).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prepare_assets_replaces_older_files() {
+        let resources = temp_dir("resources");
+        let assets = temp_dir("assets");
+        fs::write(assets.join(GEOIP), b"old").unwrap();
+        // make sure bundled files are newer
+        std::thread::sleep(Duration::from_millis(1100));
+        fs::write(resources.join(GEOIP), b"bundled").unwrap();
+        fs::write(resources.join(GEOSITE), b"bundled").unwrap();
+        fs::write(resources.join("config.json"), b"{}").unwrap();
+
+        let replaced = prepare_assets(&resources, &assets).unwrap();
+        assert_eq!(replaced, vec![GEOIP.to_owned()]);
+        assert_eq!(fs::read(assets.join(GEOIP)).unwrap(), b"bundled");
+        assert_eq!(fs::read(assets.join(GEOSITE)).unwrap(), b"bundled");
+        assert!(!assets.join("config.json").exists());
+
+        // downloaded files are newer than bundled ones
+        fs::write(assets.join(GEOIP), b"downloaded").unwrap();
+        assert!(prepare_assets(&resources, &assets).unwrap().is_empty());
+        assert_eq!(fs::read(assets.join(GEOIP)).unwrap(), b"downloaded");
+        fs::remove_dir_all(&resources).unwrap();
+        fs::remove_dir_all(&assets).unwrap();
+    }
+}
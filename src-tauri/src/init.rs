@@ -1,6 +1,7 @@
 use crate::{
-    commands::subs::check_subs_update, core::exit_core, event::RUAEvents, message::message_handler,
-    store::ui::CoreStatus, utils::get_main_window, CONFIG, CORE, CORE_SHUTDOWN, UI,
+    commands::subs::startup_subs_update, config::watcher::watch_config, core::exit_core,
+    event::RUAEvents, geo::prepare_assets, message::message_handler, store::ui::CoreStatus,
+    utils::get_main_window, CONFIG, CORE, CORE_SHUTDOWN, UI,
 };
 use anyhow::{anyhow, Ok as AOk, Result};
 use log::{error, info};
@@ -99,10 +100,38 @@ async fn init_core_and_config(resources_path: &PathBuf, window: Window) -> Resul
     info!("Start core");
     let mut core = CORE.lock().await;
     // Set v2ray assert location with environment
-    env::set_var("V2RAY_LOCATION_ASSET", resources_path);
+    // use writable asset folder, so geo data can be updated
+    let asset_dir = config.asset_dir();
+    match prepare_assets(resources_path, &asset_dir) {
+        Ok(replaced) => {
+            env::set_var("V2RAY_LOCATION_ASSET", asset_dir);
+            // downloaded versions are replaced by bundled files
+            if !replaced.is_empty() {
+                config
+                    .rua
+                    .geo
+                    .files
+                    .retain(|file| !replaced.contains(&file.name));
+                if let Err(err) = config.write_rua() {
+                    error!("Write rua config failed {err}");
+                }
+            }
+        }
+        Err(err) => {
+            error!("Prepare asset folder failed {err}, use bundled resources");
+            env::set_var("V2RAY_LOCATION_ASSET", resources_path);
+        }
+    }
+
+    // Reload config when files are edited by other programs
+    async_runtime::spawn(watch_config(
+        config.core_path.clone(),
+        config.rua_path.clone(),
+    ));
 
     let mut ui = UI.lock().await;
-    match core.init(&config.core_path).await {
+    ui.mode = config.rua.mode;
+    match core.init(&config.runtime_path).await {
         Ok(_) => {
             ui.core_status = CoreStatus::Started;
             info!("Core started");
@@ -118,10 +147,8 @@ async fn init_core_and_config(resources_path: &PathBuf, window: Window) -> Resul
 
 /// After app initialized
 async fn after_app_setup() -> Result<()> {
-    let mut config = CONFIG.lock().await;
     info!("Start init config");
-
-    check_subs_update(&mut config).await?;
+    startup_subs_update().await?;
     Ok(())
 }
 
@@ -1,35 +1,292 @@
+use crate::config::LogSetting;
 use crate::message::{ConfigMsg, MSG_TX};
 use crate::LOGGING;
-use anyhow::{Ok, Result};
+use anyhow::{anyhow, Result};
 use chrono::Local;
-use env_logger::{Builder, Env};
-use std::io::Write;
-use tauri::async_runtime;
+use env_logger::{Builder, Logger};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    env,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{atomic::Ordering, Mutex},
+    time::{Duration, SystemTime},
+};
+
+/// The log target used by v2ray core stdout and stderr
+pub const CORE_TARGET: &str = "core";
+/// Max lines kept in memory
+const RING_SIZE: usize = 2000;
+/// Current log file name, rotated files will be `venus.log.1`, `venus.log.2`...
+const LOG_NAME: &str = "venus.log";
+
+/// Where the log line comes from
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum LogSource {
+    App,
+    Core,
+}
+
+/// Single log line, stored in ring buffer
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLine {
+    pub time: String,
+    pub level: String,
+    pub source: LogSource,
+    pub message: String,
+}
+impl std::fmt::Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} - {} - {}", self.time, self.level, self.message)
+    }
+}
+
+/// Recent log lines in memory
+static RING: Lazy<Mutex<VecDeque<LogLine>>> =
+    Lazy::new(|| Mutex::new(VecDeque::with_capacity(RING_SIZE)));
+/// Log file writer, available after config initialized
+static LOG_FILE: Lazy<Mutex<Option<LogFile>>> = Lazy::new(|| Mutex::new(None));
+
+/// Log file with size and age based rotation
+struct LogFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    setting: LogSetting,
+}
+
+impl LogFile {
+    fn open(dir: &Path, setting: LogSetting) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_NAME))?;
+        let size = file.metadata()?.len();
+        let log_file = Self {
+            dir: PathBuf::from(dir),
+            file,
+            size,
+            setting,
+        };
+        log_file.clean_expired();
+        Ok(log_file)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        let max_size = self.setting.max_size * 1024 * 1024;
+        if max_size > 0 && self.size + line.len() as u64 > max_size {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{line}")?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+
+    /// Rename `venus.log.N-1` to `venus.log.N` ... `venus.log` to `venus.log.1`
+    /// and reopen a empty `venus.log`
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = |index: usize| self.dir.join(format!("{}.{}", LOG_NAME, index));
+        let max_files = self.setting.max_files.max(1);
+        let _ = fs::remove_file(rotated(max_files));
+        for index in (1..max_files).rev() {
+            let from = rotated(index);
+            if from.exists() {
+                fs::rename(from, rotated(index + 1))?;
+            }
+        }
+        fs::rename(self.dir.join(LOG_NAME), rotated(1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(LOG_NAME))?;
+        self.size = 0;
+        self.clean_expired();
+        Ok(())
+    }
+
+    /// Remove rotated log files older than `max_days`
+    fn clean_expired(&self) {
+        if self.setting.max_days == 0 {
+            return;
+        }
+        let max_age = Duration::from_secs(self.setting.max_days * 24 * 60 * 60);
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        entries
+            .flatten()
+            .filter(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with(LOG_NAME) && name != LOG_NAME
+            })
+            .for_each(|entry| {
+                let expired = entry
+                    .metadata()
+                    .and_then(|meta| meta.modified())
+                    .map(|modified| {
+                        SystemTime::now()
+                            .duration_since(modified)
+                            .unwrap_or_default()
+                            > max_age
+                    })
+                    .unwrap_or(false);
+                if expired {
+                    let _ = fs::remove_file(entry.path());
+                }
+            });
+    }
+}
+
+/// Wrap env_logger, and also push lines to ring buffer,
+/// log file and frontend
+struct VLogger {
+    inner: Logger,
+}
+
+impl Log for VLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.inner.log(record);
+
+        let line = LogLine {
+            time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            level: record.level().to_string(),
+            source: if record.target() == CORE_TARGET {
+                LogSource::Core
+            } else {
+                LogSource::App
+            },
+            message: record.args().to_string(),
+        };
+        let text = line.to_string();
+
+        if let Ok(mut file) = LOG_FILE.lock() {
+            if let Some(file) = file.as_mut() {
+                if let Err(err) = file.write_line(&text) {
+                    eprintln!("Write log file failed {err}");
+                }
+            }
+        }
+        if let Ok(mut ring) = RING.lock() {
+            if ring.len() >= RING_SIZE {
+                ring.pop_front();
+            }
+            ring.push_back(line);
+        }
+        // Lines dropped here can still be fetched from ring buffer
+        if LOGGING.load(Ordering::Relaxed) {
+            if let Ok(tx) = MSG_TX.try_lock() {
+                let _ = tx.try_send(ConfigMsg::EmitLog(text));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        if let Ok(mut file) = LOG_FILE.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.file.flush();
+            }
+        }
+    }
+}
 
 pub fn init_logger() -> Result<()> {
-    let env = Env::default().filter_or("RUA_LOG_LEVEL", "info");
-    let mut builder = Builder::from_env(env);
+    let level = env::var("RUA_LOG_LEVEL").unwrap_or_else(|_| "info".into());
+    let level = LevelFilter::from_str(&level).unwrap_or(LevelFilter::Info);
 
-    builder
+    // Level is filtered by `log::max_level()`, so it can be changed at runtime
+    let inner = Builder::new()
+        .filter_level(LevelFilter::Trace)
         .format(move |buf, record| {
             let now = Local::now();
             let formatted = format!("{}", now.format("%Y-%m-%d %H:%M:%S"));
-            let log = format!("{} - {} - {}", formatted, record.level(), record.args());
-
-            let emit_log = log.clone();
-            async_runtime::spawn(async move {
-                if LOGGING.load(std::sync::atomic::Ordering::Relaxed) {
-                    MSG_TX
-                        .lock()
-                        .await
-                        .send(ConfigMsg::EmitLog(emit_log))
-                        .await?;
-                }
-                Ok(())
-            });
-            writeln!(buf, "{log}")
+            writeln!(
+                buf,
+                "{} - {} - {}",
+                formatted,
+                record.level(),
+                record.args()
+            )
         })
-        .init();
-    // env_logger::init_from_env(env);
+        .build();
+
+    log::set_boxed_logger(Box::new(VLogger { inner }))?;
+    log::set_max_level(level);
     Ok(())
 }
+
+/// Apply log setting from config, and start write log to file.
+/// `RUA_LOG_LEVEL` environment variable overrides the level in setting.
+///
+/// ## Arguments
+///
+/// `dir`: the folder of log files
+/// `setting`: log setting in config
+pub fn apply_log_setting(dir: &Path, setting: &LogSetting) -> Result<()> {
+    if env::var("RUA_LOG_LEVEL").is_err() {
+        set_level(&setting.level)?;
+    }
+    let file = LogFile::open(dir, setting.clone())?;
+    let mut log_file = LOG_FILE.lock().map_err(|e| anyhow!("{e}"))?;
+    *log_file = Some(file);
+    Ok(())
+}
+
+/// Change log level at runtime
+pub fn set_level(level: &str) -> Result<()> {
+    let level = LevelFilter::from_str(level).map_err(|_| anyhow!("invalid log level {level}"))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Get last `lines` lines from memory
+///
+/// ## Arguments
+///
+/// `lines`: how many lines to return
+/// `level`: only return lines which level is equal or more severe than it
+/// `source`: only return lines from app or core
+pub fn last_lines(
+    lines: usize,
+    level: Option<&str>,
+    source: Option<LogSource>,
+) -> Result<Vec<LogLine>> {
+    let level = level
+        .map(|l| Level::from_str(l).map_err(|_| anyhow!("invalid log level {l}")))
+        .transpose()?;
+    let ring = RING.lock().map_err(|e| anyhow!("{e}"))?;
+    let mut result = ring
+        .iter()
+        .rev()
+        .filter(|line| source.map(|s| s == line.source).unwrap_or(true))
+        .filter(|line| {
+            level
+                .map(|level| {
+                    Level::from_str(&line.level)
+                        .map(|l| l <= level)
+                        .unwrap_or(true)
+                })
+                .unwrap_or(true)
+        })
+        .take(lines)
+        .cloned()
+        .collect::<Vec<_>>();
+    result.reverse();
+    Ok(result)
+}
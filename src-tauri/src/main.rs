@@ -5,9 +5,31 @@
 
 use crate::{
     commands::{
-        config::{get_config, read_config_file, update_config},
-        core::{restart_core, select_node},
+        balancer::{preview_balancer, set_balancer},
+        bind::{get_interfaces, set_outbound_bind},
+        bundle::{export_bundle, import_bundle, preview_bundle},
+        chain::{add_chain, delete_chain, get_chains, update_chain},
+        config::{
+            get_config, preview_core_config, read_config_file, update_config, update_overlay,
+        },
+        core::{restart_core, select_node, set_proxy_mode},
+        dns::{check_dns, import_hosts, update_dns, update_dns_hosts, update_dns_servers},
+        geo::{
+            complete_geo_matcher, get_geo_codes, rollback_geo_data, search_geo_data,
+            update_geo_data,
+        },
+        inbound::{add_inbound, check_inbound_ports, remove_inbound, update_inbound},
+        lan::{get_lan_info, set_lan_sharing},
+        logs::{get_logs, set_log_level},
+        node_override::{get_node_overrides, set_node_override, set_subscription_override},
         node_speed,
+        profile::{activate_profile, clone_profile, create_profile, delete_profile, get_profiles},
+        routing::{
+            add_rule, check_rule, delete_rule, move_rule, set_rule_enabled, simulate_route,
+            update_rule,
+        },
+        rule_set::{add_rule_set, edit_rule_set, get_rule_sets, remove_rule_set, update_rule_sets},
+        snapshot::{diff_snapshot, get_snapshots, restore_snapshot},
         subs::{add_subscription, update_all_subs, update_sub},
         ui::{exit_app, toggle_window},
     },
@@ -31,6 +53,7 @@ mod commands;
 mod config;
 mod core;
 mod event;
+mod geo;
 mod init;
 mod logger;
 mod message;
@@ -87,9 +110,78 @@ fn main() {
             get_config,
             update_config,
             read_config_file,
+            preview_core_config,
+            update_overlay,
+            // snapshots
+            get_snapshots,
+            diff_snapshot,
+            restore_snapshot,
+            // profiles
+            get_profiles,
+            create_profile,
+            clone_profile,
+            delete_profile,
+            activate_profile,
+            // settings bundle
+            export_bundle,
+            preview_bundle,
+            import_bundle,
             // core
             select_node,
             restart_core,
+            set_proxy_mode,
+            // proxy chains
+            get_chains,
+            add_chain,
+            update_chain,
+            delete_chain,
+            // node overrides
+            get_node_overrides,
+            set_node_override,
+            set_subscription_override,
+            // load balancing
+            preview_balancer,
+            set_balancer,
+            // routing rules
+            check_rule,
+            add_rule,
+            update_rule,
+            delete_rule,
+            move_rule,
+            set_rule_enabled,
+            simulate_route,
+            // rule sets
+            get_rule_sets,
+            add_rule_set,
+            edit_rule_set,
+            remove_rule_set,
+            update_rule_sets,
+            // dns
+            check_dns,
+            update_dns,
+            update_dns_servers,
+            update_dns_hosts,
+            import_hosts,
+            // inbounds
+            check_inbound_ports,
+            add_inbound,
+            update_inbound,
+            remove_inbound,
+            // outbound bind
+            get_interfaces,
+            set_outbound_bind,
+            // lan sharing
+            get_lan_info,
+            set_lan_sharing,
+            // geo data
+            update_geo_data,
+            rollback_geo_data,
+            get_geo_codes,
+            search_geo_data,
+            complete_geo_matcher,
+            // logs
+            get_logs,
+            set_log_level,
             // common commands
             node_speed,
             // ui
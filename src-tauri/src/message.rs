@@ -30,6 +30,8 @@ pub enum ConfigMsg {
     EmitLog(String),
     /// emit core and rua config to frontend
     EmitConfig,
+    /// config file edited by other program is invalid
+    ConfigError(String),
     // emit whole ui to fronted
     // EmitUI,
 }
@@ -67,6 +69,7 @@ pub fn message_handler(window: Window) -> Result<()> {
                         Ok(_) => {
                             let config = CONFIG.lock().await;
                             ui.core_status = CoreStatus::Started;
+                            ui.mode = config.rua.mode;
                             window.emit_all(UpdateUI.into(), &*ui)?;
                             window.emit_all(UpdateCoreConfig.into(), &config.core)?;
                             window.emit_all(UpdateRuaConfig.into(), &config.rua)?;
@@ -75,6 +78,7 @@ pub fn message_handler(window: Window) -> Result<()> {
                             error!("Core restart failed {err}");
                             let config = CONFIG.lock().await;
                             ui.core_status = CoreStatus::Stopped;
+                            ui.mode = config.rua.mode;
                             window.emit_all(UpdateUI.into(), &*ui)?;
                             window.emit_all(UpdateCoreConfig.into(), &config.core)?;
                             window.emit_all(UpdateRuaConfig.into(), &config.rua)?;
@@ -88,6 +92,15 @@ pub fn message_handler(window: Window) -> Result<()> {
                     let config = CONFIG.lock().await;
                     window.emit(UpdateRuaConfig.into(), &config.rua)?;
                     window.emit(UpdateCoreConfig.into(), &config.core)?;
+                    // mode may be changed by reloaded config
+                    let mode = config.rua.mode;
+                    drop(config);
+                    let mut ui = UI.lock().await;
+                    ui.mode = mode;
+                    window.emit_all(UpdateUI.into(), &*ui)?;
+                }
+                ConfigMsg::ConfigError(err) => {
+                    window.emit_all(ConfigError.into(), err)?;
                 } /* ConfigMsg::EmitUI => {
                       let ui = UI.lock().await;
                       window.emit_all(UpdateUI.into(), &*ui)?;
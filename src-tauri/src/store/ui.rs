@@ -1,3 +1,4 @@
+use crate::config::ProxyMode;
 use serde::{Deserialize, Serialize};
 
 /// 用于前端的全局状态
@@ -8,6 +9,8 @@ pub struct UI {
     pub core_status: CoreStatus,
     /// V2ray core version
     pub core_version: String,
+    /// Active routing mode
+    pub mode: ProxyMode,
 }
 
 impl Default for UI {
@@ -16,6 +19,7 @@ impl Default for UI {
         UI {
             core_status: Stopped,
             core_version: String::new(),
+            mode: ProxyMode::default(),
         }
     }
 }
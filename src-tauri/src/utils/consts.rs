@@ -1,6 +1,14 @@
 /// Default speed test url
 pub static SPEED_URL: &str = "https://sabnzbd.org/tests/internetspeed/50MB.bin";
 
+/// Default probe url of observatory used by load balancing
+pub static PROBE_URL: &str = "https://www.google.com/generate_204";
+
+/// Default geo data download url, checksum is `{url}.sha256sum`
+pub static GEOIP_URL: &str = "https://github.com/v2fly/geoip/releases/latest/download/geoip.dat";
+pub static GEOSITE_URL: &str =
+    "https://github.com/v2fly/domain-list-community/releases/latest/download/dlc.dat";
+
 /// info from package
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
 pub static NAME: &str = env!("CARGO_PKG_NAME");
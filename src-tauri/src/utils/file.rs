@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+/// Write file atomically, write to a temp file in the same folder,
+/// sync it to disk, then rename it to target path.
+/// A crash or full disk will never leave a half-written file.
+///
+/// ## Arguments
+///
+/// `path`: target file path
+/// `content`: file content
+pub fn atomic_write(path: &Path, content: &[u8]) -> Result<()> {
+    let parent = path
+        .parent()
+        .ok_or(anyhow!("parent of {} is empty", path.display()))?;
+    let name = path
+        .file_name()
+        .ok_or(anyhow!("file name of {} is empty", path.display()))?;
+    fs::create_dir_all(parent)?;
+
+    let temp_path = parent.join(format!(".{}.tmp", name.to_string_lossy()));
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    // Make sure the rename is on disk
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Move file or folder, copy it if rename failed like across file systems
+///
+/// ## Arguments
+///
+/// `from`: source path
+/// `to`: target path, parent folders will be created
+pub fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_path(from, to)?;
+    if from.is_dir() {
+        fs::remove_dir_all(from)?;
+    } else {
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Copy file or folder recursively
+fn copy_path(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
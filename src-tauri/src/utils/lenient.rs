@@ -0,0 +1,67 @@
+//! Lenient deserializers for share links from different providers,
+//! they may use numbers or booleans where strings are expected.
+
+use serde::{de::Error, Deserialize, Deserializer};
+use serde_json::{Map, Value};
+
+/// Accept string, number, boolean or null as string, null becomes empty
+pub fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Null => Ok(String::new()),
+        other => Err(D::Error::custom(format!(
+            "expected string or number, got {}",
+            other
+        ))),
+    }
+}
+
+/// Accept boolean, `0`/`1` and `"true"`/`"false"`, null or empty string becomes None
+pub fn lenient_bool<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Bool(b) => Ok(Some(b)),
+        Value::Number(n) => Ok(Some(n.as_f64().unwrap_or(0.0) != 0.0)),
+        Value::String(s) => match s.trim().to_lowercase().as_str() {
+            "" => Ok(None),
+            "1" | "true" => Ok(Some(true)),
+            "0" | "false" => Ok(Some(false)),
+            other => Err(D::Error::custom(format!("expected boolean, got {}", other))),
+        },
+        Value::Null => Ok(None),
+        other => Err(D::Error::custom(format!("expected boolean, got {}", other))),
+    }
+}
+
+/// Accept any map and drop null values recursively, toml has no null
+pub fn without_null<'de, D>(deserializer: D) -> Result<Map<String, Value>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut map = Map::deserialize(deserializer)?;
+    strip_null(&mut map);
+    Ok(map)
+}
+
+fn strip_null(map: &mut Map<String, Value>) {
+    map.retain(|_, value| !value.is_null());
+    map.values_mut().for_each(strip_value);
+}
+
+fn strip_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => strip_null(map),
+        Value::Array(items) => {
+            items.retain(|item| !item.is_null());
+            items.iter_mut().for_each(strip_value);
+        }
+        _ => {}
+    }
+}
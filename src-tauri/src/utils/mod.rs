@@ -4,6 +4,8 @@ use tauri::{App, Window};
 
 pub mod consts;
 pub mod error;
+pub mod file;
+pub mod lenient;
 pub mod timer;
 
 /// Get main window by app
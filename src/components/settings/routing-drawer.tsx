@@ -9,6 +9,7 @@ import { useCallback } from 'react';
 import useStore from 'store';
 import { Rule } from 'store/config-store';
 import { useImmer } from 'use-immer';
+import { registerGeoCompletion } from 'utils/geo-completion';
 
 const keys = ['ip', 'domain', 'source', 'inboundTag', 'attrs', 'protocol'];
 
@@ -113,12 +114,14 @@ const RoutingDrawer = ({
         }}
         onChange={updateField('ip')}
         value={buffer.ip}
+        beforeMount={registerGeoCompletion}
       />
       <DrawerMonaco
         label="Domain"
         language="json"
         value={buffer.domain}
         onChange={updateField('domain')}
+        beforeMount={registerGeoCompletion}
       />
       <DrawerSelect
         label="Outbound tag"
@@ -136,7 +136,8 @@ export interface Inbound {
 
 export interface InboundSettings {
   auth: string;
-  udp: boolean;
+  udp?: boolean;
+  allowTransparent?: boolean;
   ip: string;
 }
 
@@ -147,7 +148,7 @@ export interface Sniffing {
 }
 
 export interface Log {
-  loglevel: string;
+  loglevel?: string;
   access: null;
   error: null;
 }
@@ -158,10 +159,26 @@ export interface Outbound {
   protocol: string;
   settings: OutboundSettings;
   tag: string;
+  streamSettings?: StreamSettings | null;
   proxySetting: null;
   mux: null;
 }
 
+export interface StreamSettings {
+  network?: string;
+  security?: string;
+  tlsSettings?: TlsSettings | null;
+}
+
+export interface TlsSettings {
+  serverName?: string;
+  allowInsecure?: boolean;
+  alpn?: string[];
+  certificates?: unknown[];
+  disableSystemRoot?: boolean;
+  fingerprint?: string;
+}
+
 export interface OutboundSettings {
   vnext: Vnext[] | null;
 }
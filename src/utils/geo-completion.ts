@@ -0,0 +1,46 @@
+import { Monaco } from '@monaco-editor/react';
+import { invoke } from '@tauri-apps/api/tauri';
+
+let registered = false;
+
+/**
+ * Offer `geosite:` and `geoip:` completions in routing rule editors,
+ * the valid names are read from geo data files by backend.
+ */
+export const registerGeoCompletion = (monaco: Monaco) => {
+  if (registered) return;
+  registered = true;
+  monaco.languages.registerCompletionItemProvider('json', {
+    triggerCharacters: [':', '@', '!'],
+    provideCompletionItems: async (model, position) => {
+      const line = model.getLineContent(position.lineNumber);
+      const before = line.slice(0, position.column - 1);
+      const start = before.lastIndexOf('"') + 1;
+      const input = before.slice(start);
+      if (!/^(geosite|geoip):/.test(input)) return { suggestions: [] };
+
+      try {
+        const matchers = await invoke<string[]>('complete_geo_matcher', {
+          input,
+        });
+        const range = {
+          startLineNumber: position.lineNumber,
+          endLineNumber: position.lineNumber,
+          startColumn: start + 1,
+          endColumn: position.column,
+        };
+        return {
+          suggestions: matchers.map((matcher) => ({
+            label: matcher,
+            kind: monaco.languages.CompletionItemKind.Value,
+            insertText: matcher,
+            range,
+          })),
+        };
+      } catch (err) {
+        console.error(err);
+        return { suggestions: [] };
+      }
+    },
+  });
+};